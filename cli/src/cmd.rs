@@ -1,12 +1,18 @@
+pub mod add_test;
+pub mod clean;
 pub mod contest;
 pub mod expand;
 pub mod fetch;
+pub mod import_tests;
 pub mod init;
 pub mod langs;
 pub mod login;
 pub mod logout;
 pub mod root;
+pub mod run;
 pub mod shojin;
+pub mod shrink;
+pub mod stress;
 pub mod submit;
 pub mod test;
 
@@ -24,15 +30,21 @@ pub struct GlobalArgs {
 
 #[derive(Debug, clap::Subcommand)]
 pub enum Subcommand {
+    AddTest(add_test::Args),
+    Clean(clean::Args),
     Contest(contest::Args),
     Expand(expand::Args),
     Fetch(fetch::Args),
+    ImportTests(import_tests::Args),
     Init(init::Args),
     Langs(langs::Args),
     Login(login::Args),
     Logout(logout::Args),
     Root(root::Args),
+    Run(run::Args),
     Shojin(shojin::Args),
+    Shrink(shrink::Args),
+    Stress(stress::Args),
 
     #[command(alias("t"))]
     Test(test::Args),
@@ -47,15 +59,21 @@ impl GlobalArgs {
     pub async fn exec_subcmd(&self) -> SubcmdResult {
         use Subcommand::*;
         match &self.subcmd {
+            AddTest(args) => add_test::exec(args, self),
+            Clean(args) => clean::exec(args, self),
             Contest(args) => contest::exec(args, self).await,
             Expand(args) => expand::exec(args, self),
             Fetch(args) => fetch::exec(args, self).await,
+            ImportTests(args) => import_tests::exec(args, self).await,
             Init(args) => init::exec(args, self),
             Langs(args) => langs::exec(args, self).await,
             Login(args) => login::exec(args, self).await,
             Logout(args) => logout::exec(args, self).await,
             Root(args) => root::exec(args, self),
+            Run(args) => run::exec(args, self).await,
             Shojin(args) => shojin::exec(args, self).await,
+            Shrink(args) => shrink::exec(args, self).await,
+            Stress(args) => stress::exec(args, self).await,
             Submit(args) => submit::exec(args, self).await,
             Test(args) => test::exec(args, self).await,
         }
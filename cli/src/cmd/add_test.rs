@@ -0,0 +1,84 @@
+use std::{
+    io::{self, IsTerminal as _, Read as _},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, ensure, Context as _};
+use colored::Colorize;
+use kpr_core::{interactive, storage::ProblemWorkspace};
+
+use super::{GlobalArgs, SubcmdResult};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Name of the testcase [default: "user{N}"]
+    #[arg()]
+    pub name: Option<String>,
+
+    /// File of the input ("-" for stdin) [default: open $EDITOR]
+    #[arg(short, long)]
+    pub input: Option<PathBuf>,
+
+    /// File of the expected output ("-" for stdin) [default: open $EDITOR]
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+pub fn exec(args: &Args, _global_args: &GlobalArgs) -> SubcmdResult {
+    ensure!(
+        !(is_stdin(&args.input) && is_stdin(&args.output)),
+        "Cannot read both of the input and the expected output from stdin"
+    );
+
+    let workspace = ProblemWorkspace::new(Path::new("."));
+    let name = match &args.name {
+        Some(name) => name.to_owned(),
+        None => workspace.unused_testcase_name("user"),
+    };
+
+    let input = self::read_text(&args.input, "input")?;
+    let output = self::read_text(&args.output, "expected output")?;
+
+    let (infile, outfile) = workspace
+        .save_user_testcase(&name, input, output)
+        .context("Failed to save the testcase")?;
+    println!(
+        "{}\n  {}\n  {}",
+        format!("Added testcase '{}':", name).green(),
+        infile.to_string_lossy(),
+        outfile.to_string_lossy()
+    );
+    Ok(())
+}
+
+fn is_stdin(path: &Option<PathBuf>) -> bool {
+    path.as_deref() == Some(Path::new("-"))
+}
+
+fn read_text(path: &Option<PathBuf>, what: &str) -> anyhow::Result<String> {
+    match path {
+        Some(_) if self::is_stdin(path) => {
+            let mut s = String::new();
+            io::stdin()
+                .read_to_string(&mut s)
+                .with_context(|| format!("Failed to read the {} from stdin", what))?;
+            Ok(s)
+        }
+        Some(path) => Ok(fsutil::read_to_string(path)?),
+        None => {
+            ensure!(
+                io::stdin().is_terminal(),
+                "Specify the file of the {} (or \"-\" for stdin)",
+                what
+            );
+            println!("Opening the editor for the {} ...", what);
+            let Some(mut text) = interactive::util::ask_text_with_editor("")? else {
+                bail!("Canceled: the {} was not saved", what)
+            };
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+            Ok(text)
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use kpr_core::{action, config::Config, print_success, storage::Repository};
+
+use crate::util;
+
+use super::{GlobalArgs, SubcmdResult};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {}
+
+pub fn exec(_args: &Args, _global_args: &GlobalArgs) -> SubcmdResult {
+    let cfg = Config::from_file_finding_in_ancestors(util::current_dir())?;
+    let test_cfg = cfg.test.clone();
+    let repo: Repository = cfg.into();
+
+    let removed = action::clean_build_artifacts(&repo, &test_cfg)?;
+    for path in &removed {
+        let path = fsutil::relative_path(util::current_dir(), path);
+        println!("Removed {}", path.to_string_lossy());
+    }
+    print_success!("Removed {} build artifacts ✨", removed.len());
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use kpr_core::{
+    action,
+    client::SessionPersistentClient,
+    print_success,
+    storage::{ProblemVault, ProblemWorkspace, Repository},
+};
+
+use super::{GlobalArgs, SubcmdResult};
+use crate::{config::GlobalConfig, util};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Zip, tar.gz or dir of testcases (e.g. `in/*.txt` and `out/*.txt`)
+    #[arg()] // positional argument
+    pub source: PathBuf,
+
+    /// Problem to import the testcases into [default: the problem of the current workspace]
+    #[arg()] // positional argument
+    pub problem_url: Option<String>,
+
+    /// Name of the testcase set to import into, replacing its old testcases
+    /// ("sample" to add them to the samples)
+    #[arg(long, value_name = "NAME", default_value = "full")]
+    pub set: String,
+}
+
+pub async fn exec(args: &Args, global_args: &GlobalArgs) -> SubcmdResult {
+    let vault = match &args.problem_url {
+        Some(problem_url) => {
+            let cfg = GlobalConfig::from_file_and_args(global_args);
+            let (cli, url) =
+                SessionPersistentClient::new_with_parse_url(problem_url, &cfg.cache_dir)?;
+            let repo: Repository =
+                kpr_core::Config::from_file_finding_in_ancestors(util::current_dir())?.into();
+            let (vault, _) = action::ensure_problem_data_saved(&cli, &url, &repo).await?;
+            vault
+        }
+        None => ProblemWorkspace::new(Path::new("."))
+            .problem_vault()
+            .context("Not in a problem workspace (Specify the problem url)")?,
+    };
+
+    let names = action::import_testcases(&args.source, &vault, &args.set)?;
+    print_success!(
+        "Imported {} testcases into '{}' ✨",
+        names.len(),
+        vault.testcase_set_dir(&args.set).to_string_lossy()
+    );
+    if args.set != ProblemVault::SAMPLE_SET {
+        println!("Run them by `kpr test --set {}`", args.set);
+    }
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+
+use kpr_core::{
+    action::{self, TestOptions},
+    config::Config,
+    storage::ProblemWorkspace,
+};
+
+use crate::util;
+
+use super::{GlobalArgs, SubcmdResult};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[arg()] // positional argument
+    pub program_file_or_workspace_dir: Option<PathBuf>,
+
+    /// File passed to stdin [default: the terminal]
+    #[arg(short, long, value_name = "FILE")]
+    pub input: Option<PathBuf>,
+
+    /// Compile even if the sources and the compile command are unchanged since the last compilation
+    #[arg(long)]
+    pub force_compile: bool,
+
+    /// Build and run with the profile of `test.command[]` (e.g. debug) [default: release]
+    #[arg(short, long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Run the program with resource limits in a fresh working dir (see `test.sandbox` in config)
+    #[arg(long)]
+    pub sandbox: bool,
+}
+
+pub async fn exec(args: &Args, _global_args: &GlobalArgs) -> SubcmdResult {
+    let cfg = Config::from_file_finding_in_ancestors(util::current_dir())?;
+    let workspace = ProblemWorkspace::new(Path::new("."));
+
+    let program_file =
+        util::determine_program_file(&args.program_file_or_workspace_dir, &cfg.test.include)?;
+
+    let mut opts = TestOptions::from_workspace(&workspace, &cfg)?;
+    // Nothing is judged.
+    opts.checker = None;
+    opts.interactor = None;
+    opts.force_compile = args.force_compile;
+    opts.sandbox = args.sandbox;
+    opts.profile = args.profile.clone();
+
+    let res = action::run_program(&program_file, args.input.as_deref(), &cfg.test, &opts).await?;
+    if res.status != Some(0) {
+        std::process::exit(res.status.unwrap_or(1));
+    }
+    Ok(())
+}
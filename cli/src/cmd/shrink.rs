@@ -0,0 +1,101 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use colored::Colorize;
+use kpr_core::{
+    action::{self, StressOptions, TestOptions},
+    config::Config,
+    storage::ProblemWorkspace,
+};
+
+use crate::util;
+
+use super::{GlobalArgs, SubcmdResult};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Input on which the program fails
+    #[arg()]
+    pub input_file: PathBuf,
+
+    /// Naive (but surely correct) solution which makes the expected output
+    #[arg()]
+    pub naive: PathBuf,
+
+    #[arg()]
+    pub program_file_or_workspace_dir: Option<PathBuf>,
+
+    /// Program which rejects (by non-zero exit code) or fixes (by printing) a shrunk input
+    #[arg(long)]
+    pub validator: Option<PathBuf>,
+
+    /// Time limit of the naive solution and the validator in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = StressOptions::DEFAULT_NAIVE_TIME_LIMIT.as_millis() as u64)]
+    pub naive_time_limit: u64,
+
+    /// Write the shrunk input to the file
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Save the shrunk input as a new testcase of the workspace
+    #[arg(long)]
+    pub save: bool,
+
+    /// Compile even if the sources and the compile command are unchanged since the last compilation
+    #[arg(long)]
+    pub force_compile: bool,
+}
+
+pub async fn exec(args: &Args, _global_args: &GlobalArgs) -> SubcmdResult {
+    let cfg = Config::from_file_finding_in_ancestors(util::current_dir())?;
+    let workspace = ProblemWorkspace::new(Path::new("."));
+
+    let program_file =
+        util::determine_program_file(&args.program_file_or_workspace_dir, &cfg.test.include)?;
+    let input = fsutil::read_to_string(&args.input_file)?;
+
+    let mut test_opts = TestOptions::from_workspace(&workspace, &cfg)?;
+    test_opts.force_compile = args.force_compile;
+    let res = action::do_shrink(
+        input,
+        &program_file,
+        &args.naive,
+        args.validator.as_ref(),
+        Duration::from_millis(args.naive_time_limit),
+        &cfg.test,
+        &test_opts,
+    )
+    .await?;
+
+    println!(
+        "{}",
+        format!(
+            "Shrunk the input keeping {} ({} bytes, {} trials):",
+            res.outcome.judge,
+            res.input.len(),
+            res.num_trials
+        )
+        .green()
+    );
+    print!("{}", res.input);
+
+    if let Some(output) = &args.output {
+        fsutil::write(output, &res.input)?;
+        println!("Written to {}", output.to_string_lossy());
+    }
+    if args.save {
+        let name = workspace.unused_testcase_name("shrink");
+        let (infile, outfile) = workspace
+            .save_user_testcase(&name, &res.input, &res.outcome.groundtruth)
+            .context("Failed to save the shrunk input")?;
+        println!(
+            "Saved as:\n  {}\n  {}",
+            infile.to_string_lossy(),
+            outfile.to_string_lossy()
+        );
+    }
+    Ok(())
+}
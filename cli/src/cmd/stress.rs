@@ -0,0 +1,99 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::builder::RangedU64ValueParser;
+use colored::Colorize;
+use kpr_core::{
+    action::{self, StressOptions, TestOptions},
+    config::Config,
+    storage::ProblemWorkspace,
+};
+
+use crate::util;
+
+use super::{GlobalArgs, SubcmdResult};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Program which prints a random input (invoked as `<run> <seed>`)
+    #[arg()]
+    pub generator: PathBuf,
+
+    /// Naive (but surely correct) solution
+    #[arg()]
+    pub naive: PathBuf,
+
+    #[arg()]
+    pub program_file_or_workspace_dir: Option<PathBuf>,
+
+    /// Seed for the first iteration (incremented for each iteration) [default: random]
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Max number of iterations
+    #[arg(short = 'n', long, default_value_t = 1000, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub iterations: usize,
+
+    /// Time limit of the naive solution and the generator in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = StressOptions::DEFAULT_NAIVE_TIME_LIMIT.as_millis() as u64)]
+    pub naive_time_limit: u64,
+
+    /// Compile even if the sources and the compile command are unchanged since the last compilation
+    #[arg(long)]
+    pub force_compile: bool,
+}
+
+pub async fn exec(args: &Args, _global_args: &GlobalArgs) -> SubcmdResult {
+    let cfg = Config::from_file_finding_in_ancestors(util::current_dir())?;
+    let workspace = ProblemWorkspace::new(Path::new("."));
+
+    let program_file =
+        util::determine_program_file(&args.program_file_or_workspace_dir, &cfg.test.include)?;
+
+    let mut test_opts = TestOptions::from_workspace(&workspace, &cfg)?;
+    test_opts.force_compile = args.force_compile;
+    let stress_opts = StressOptions {
+        seed: args.seed.unwrap_or_else(rand::random),
+        max_iterations: args.iterations,
+        naive_time_limit: Duration::from_millis(args.naive_time_limit),
+    };
+    println!(
+        "Stress testing {:?} (seed={}, iterations={})",
+        program_file, stress_opts.seed, stress_opts.max_iterations
+    );
+
+    let counterexample = action::do_stress_test(
+        &program_file,
+        &args.naive,
+        &args.generator,
+        &workspace,
+        &cfg.test,
+        &test_opts,
+        &stress_opts,
+    )
+    .await?;
+
+    match counterexample {
+        Some(c) => println!(
+            "{}\n  {}\n  {}",
+            format!(
+                "Found a counterexample ({}, seed={}). Saved as:",
+                c.outcome.judge, c.seed
+            )
+            .bright_red(),
+            c.input_file.to_string_lossy(),
+            c.output_file.to_string_lossy(),
+        ),
+        None => println!(
+            "{}",
+            format!(
+                "No counterexample found in {} iterations ✨",
+                stress_opts.max_iterations
+            )
+            .green()
+        ),
+    }
+    Ok(())
+}
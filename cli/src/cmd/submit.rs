@@ -3,7 +3,7 @@ use std::path::{Path, PathBuf};
 use anyhow::{ensure, Context as _};
 use colored::Colorize;
 use kpr_core::{
-    action::{self, ensure_submittable_lang_list_saved},
+    action::{self, ensure_submittable_lang_list_saved, TestOptions},
     client::SessionPersistentClient,
     config::Config,
     storage::{ProblemWorkspace, Repository},
@@ -44,8 +44,17 @@ pub async fn exec(args: &Args, global_args: &GlobalArgs) -> SubcmdResult {
     // (論理式は簡略化済み)
     let run_test = args.test | (cfg.submit.run_test & !args.no_test);
     if run_test {
-        let res = action::do_test(&program_file, workspace.testcase_dir(), &cfg.test).await?;
-        if res.iter().any(|x| x.judge != JudgeCode::AC) {
+        let opts = TestOptions::from_workspace(&workspace, &cfg)?;
+        let res =
+            action::do_test(&program_file, workspace.testcase_dir(), &cfg.test, &opts).await?;
+        if res.iter().any(|x| x.judge == JudgeCode::CE) {
+            println!(
+                "{}",
+                "Canceling submission due to compile error.".bright_red()
+            );
+            return Ok(());
+        }
+        if res.iter().any(|x| x.judge.is_failure()) {
             println!(
                 "{}",
                 "Canceling submission due to test failure.".bright_red()
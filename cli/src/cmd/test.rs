@@ -1,6 +1,17 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use kpr_core::{action, config::Config, storage::ProblemWorkspace, testing::TestCommand};
+use anyhow::{ensure, Context as _};
+use clap::builder::RangedU64ValueParser;
+use kpr_core::{
+    action::{self, TestOptions},
+    config::Config,
+    storage::ProblemWorkspace,
+    testing::{report, Comparator, ReportFormat, TestCommand},
+};
+use serdable::GlobPattern;
 
 use crate::util;
 
@@ -14,31 +25,147 @@ pub struct Args {
     #[arg(short = 'd', long)]
     pub testcase_dir: Option<PathBuf>,
 
+    /// Run the testcase set imported by `kpr import-tests` (e.g. full) instead of the samples
+    #[arg(long, value_name = "NAME", conflicts_with = "testcase_dir")]
+    pub set: Option<String>,
+
     #[arg(short, long)]
     pub cmd: Option<String>,
+
+    /// exact | token | float[:<eps>[:<rel_eps>]] | ignore-case
+    #[arg(long)]
+    pub judge: Option<Comparator>,
+
+    /// Max number of testcases to run concurrently
+    #[arg(short, long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub jobs: Option<usize>,
+
+    /// Time limit in milliseconds [default: the problem's one x `test.time_limit_factor`]
+    #[arg(short = 't', long, value_name = "MS")]
+    pub time_limit: Option<u64>,
+
+    /// Run each testcase N times and report min/median/max of execution times
+    #[arg(long, value_name = "N", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub repeat: Option<usize>,
+
+    /// Compile even if the sources and the compile command are unchanged since the last compilation
+    #[arg(long)]
+    pub force_compile: bool,
+
+    /// Write the results in the format: json | junit
+    #[arg(long, value_name = "FORMAT", requires = "report_file")]
+    pub report: Option<ReportFormat>,
+
+    #[arg(long, value_name = "PATH", requires = "report")]
+    pub report_file: Option<PathBuf>,
+
+    /// Build and run with the profile of `test.command[]` (e.g. debug) [default: release]
+    #[arg(short, long, value_name = "NAME", conflicts_with = "cmd")]
+    pub profile: Option<String>,
+
+    /// Run the solution with resource limits in a fresh working dir (see `test.sandbox` in config)
+    #[arg(long)]
+    pub sandbox: bool,
+
+    /// Run only the testcases whose names match the glob pattern (can be repeated)
+    #[arg(long, value_name = "GLOB", value_parser = GlobPattern::parse)]
+    pub only: Vec<GlobPattern>,
+
+    /// Skip the testcases whose names match the glob pattern (can be repeated)
+    #[arg(long, value_name = "GLOB", value_parser = GlobPattern::parse)]
+    pub skip: Vec<GlobPattern>,
+
+    /// Don't start the remaining testcases after the first failure
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Save the outputs of the reference solution as the expected outputs of input-only testcases
+    /// before testing
+    #[arg(long, requires = "with")]
+    pub record: bool,
+
+    /// Trusted solution used by `--record`
+    #[arg(long, value_name = "PROGRAM_FILE", requires = "record")]
+    pub with: Option<PathBuf>,
+
+    /// Re-run the tests whenever the program file, its headers or the testcases are changed
+    #[arg(short, long, conflicts_with_all = ["cmd", "report"])]
+    pub watch: bool,
 }
 
 pub async fn exec(args: &Args, _global_args: &GlobalArgs) -> SubcmdResult {
     let cfg = Config::from_file_finding_in_ancestors(util::current_dir())?;
+    let workspace = ProblemWorkspace::new(Path::new("."));
+
+    let testcase_dir = match (&args.testcase_dir, &args.set) {
+        (Some(dir), _) => dir.clone(),
+        (None, Some(set)) => {
+            let dir = workspace
+                .problem_vault()
+                .context("Cannot find the vault of the problem")?
+                .testcase_set_dir(set);
+            ensure!(
+                dir.is_dir(),
+                "Testcase set '{}' not found (Import it by `kpr import-tests`)",
+                set
+            );
+            dir
+        }
+        (None, None) => workspace.testcase_dir(),
+    };
 
-    let testcase_dir = args
-        .testcase_dir
-        .clone()
-        .unwrap_or_else(|| ProblemWorkspace::new(Path::new(".")).testcase_dir());
+    let mut opts = TestOptions::from_workspace(&workspace, &cfg)?;
+    if args.testcase_dir.is_some() || args.set.is_some() {
+        opts.user_testcase_dir = None;
+    }
+    if args.judge.is_some() {
+        opts.comparator = args.judge;
+    }
+    if let Some(ms) = args.time_limit {
+        opts.execution_time_limit = Some(Duration::from_millis(ms));
+    }
+    opts.jobs = args.jobs;
+    opts.repeat = args.repeat;
+    opts.force_compile = args.force_compile;
+    opts.sandbox = args.sandbox;
+    opts.profile = args.profile.clone();
+    opts.only = args.only.clone();
+    opts.skip = args.skip.clone();
+    opts.fail_fast = args.fail_fast;
 
-    if let Some(run_cmd) = args.cmd.as_ref() {
+    if let (true, Some(reference_file)) = (args.record, &args.with) {
+        action::record_groundtruths(reference_file, &testcase_dir, &cfg.test, &opts).await?;
+    }
+
+    let (results, suite_name) = if let Some(run_cmd) = args.cmd.as_ref() {
         let cmd = TestCommand {
             compile: None,
             run: run_cmd.to_owned(),
+            ..Default::default()
         };
-        action::do_test_with_command(cmd, testcase_dir, &cfg.test).await?;
-        return Ok(());
-    }
+        let results = action::do_test_with_command(cmd, testcase_dir, &cfg.test, &opts).await?;
+        (results, run_cmd.to_owned())
+    } else {
+        let program_file =
+            util::determine_program_file(&args.program_file_or_workspace_dir, &cfg.test.include)?;
+        if args.watch {
+            return action::watch_test(&program_file, testcase_dir, &cfg.test, &opts).await;
+        }
+        let results = action::do_test(&program_file, testcase_dir, &cfg.test, &opts).await?;
+        let suite_name = program_file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        (results, suite_name)
+    };
 
-    let program_file =
-        util::determine_program_file(&args.program_file_or_workspace_dir, &cfg.test.include)?;
-
-    let _ = action::do_test(program_file, testcase_dir, &cfg.test).await?;
+    if let (Some(format), Some(report_file)) = (args.report, &args.report_file) {
+        report::write_report(report_file, format, &suite_name, &results)?;
+    }
 
+    if results.iter().any(|x| x.judge.is_failure()) {
+        std::process::exit(1);
+    }
     Ok(())
 }
@@ -4,24 +4,42 @@ pub mod error {
     pub use anyhow::{Error, Result};
 }
 
-use std::{ffi::OsStr, path::Path};
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    io::IsTerminal as _,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use chrono::{DateTime, Local};
 use colored::Colorize;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use kpr_expander::ExpanderError;
 use kpr_webclient::{problem_id::ProblemGlobalId, PgLang, ProblemInfo, SampleTestcase, Url};
+use serdable::GlobPattern;
+use tokio::{sync::Semaphore, task::JoinSet};
 
 use self::error::*;
 use crate::{
     client::SessionPersistentClient,
-    config::{ExpanderConfig, TestConfig},
+    config::{ExpanderConfig, TestConfig, WorkspaceTestConfig},
     interactive::{ask_credential, SpinnerExt as _},
     storage::{
-        workspace, PlatformVault, ProblemVault, ProblemWorkspace, Repository, WorkspaceNameModifier,
+        archive, workspace, PlatformVault, ProblemVault, ProblemWorkspace, Repository,
+        WorkspaceNameModifier,
     },
     style,
-    testing::{AsyncTestcase, FsTestcase, JudgeCode, TestCommand, TestOutcome, TestRunner},
+    testing::{
+        compile_cache, AsyncTestcase, Checker, Comparator, CompileCache, CompileError, FsTestcase,
+        Interactor, JudgeCode, OnMemoryTestcase, ProgramValidator, RunOutcome, Sandbox,
+        ShrinkResult, Shrinker, TestCommand, TestOutcome, TestRunner, TestcaseOrigin, TimingStats,
+    },
     Config,
 };
 
@@ -165,6 +183,65 @@ pub async fn create_shojin_workspace(
     Ok(loc)
 }
 
+/// Copy the testcases in the archive (zip, tar.gz or tar) or the dir into the testcase set of
+/// the vault, replacing the testcases imported before (the samples are kept and added to).
+/// Returns the names of the imported testcases.
+pub fn import_testcases(source: &Path, vault: &ProblemVault, set: &str) -> Result<Vec<String>> {
+    let tmpdir;
+    let source_dir = if source.is_dir() {
+        source
+    } else {
+        tmpdir = tempfile::tempdir().context("Failed to create temporary dir")?;
+        archive::extract(source, tmpdir.path())?;
+        tmpdir.path()
+    };
+
+    let testcases = archive::find_testcases(source_dir)?;
+    ensure!(
+        !testcases.is_empty(),
+        "No testcases found in {:?} (Expected `in/` and `out/` dirs, `*.in` and `*.out` files, ...)",
+        source
+    );
+
+    let dest_dir = vault.testcase_set_dir(set);
+    if set != ProblemVault::SAMPLE_SET && dest_dir.exists() {
+        std::fs::remove_dir_all(&dest_dir)
+            .with_context(|| format!("Failed to remove the old testcases in {:?}", dest_dir))?;
+    }
+    fsutil::mkdir_all(&dest_dir)?;
+    for t in &testcases {
+        let (infile, outfile) = ProblemVault::testcase_filename(&t.name);
+        fsutil::copy_file(&t.input, dest_dir.join(infile))?;
+        if let Some(output) = &t.output {
+            fsutil::copy_file(output, dest_dir.join(outfile))?;
+        }
+    }
+    Ok(testcases.into_iter().map(|t| t.name).collect())
+}
+
+/// Remove the build dirs of `test.command[]` and the compile caches in all the workspaces.
+/// Returns the removed paths.
+pub fn clean_build_artifacts(repo: &Repository, cfg: &TestConfig) -> Result<Vec<PathBuf>> {
+    let mut build_dirs = Vec::new();
+    for entry in &cfg.command {
+        let dir = fsutil::normalize_path(entry.build_dir_or_default());
+        // The dir may be shared with other workspaces or contain the sources.
+        if dir.is_absolute() || dir.starts_with("..") || dir == Path::new(".") {
+            log::warn!("Skipped build dir outside of workspaces: {:?}", dir);
+            continue;
+        }
+        if !build_dirs.contains(&dir) {
+            build_dirs.push(dir);
+        }
+    }
+
+    let mut removed = Vec::new();
+    for workspace in repo.workspace_home().find_workspaces()? {
+        removed.extend(workspace.remove_build_artifacts(&build_dirs)?);
+    }
+    Ok(removed)
+}
+
 pub async fn create_contest_workspace(
     cli: &SessionPersistentClient,
     contest_url: &Url,
@@ -245,29 +322,136 @@ pub async fn create_contest_workspace(
     Ok(workspace_locations)
 }
 
+/// Options for `do_test*()` which override values of `kpr.toml`.
+#[derive(Debug, Clone, Default)]
+pub struct TestOptions {
+    pub comparator: Option<Comparator>,
+
+    /// Source file of the special judge program
+    pub checker: Option<PathBuf>,
+
+    /// Source file of the interactor for interactive problems
+    pub interactor: Option<PathBuf>,
+
+    /// Max number of testcases to run concurrently
+    pub jobs: Option<usize>,
+
+    /// Judge as MLE if the peak memory usage exceeds this limit
+    pub memory_limit_kb: Option<u64>,
+
+    /// Judge as TLE if the execution time exceeds this limit
+    pub execution_time_limit: Option<Duration>,
+
+    /// Number of times to run each testcase for measuring execution time
+    pub repeat: Option<usize>,
+
+    /// Run the solution in the sandbox even if `test.sandbox.enabled` is false
+    pub sandbox: bool,
+
+    /// Dir of testcases added by the user, which are run in addition to the samples
+    pub user_testcase_dir: Option<PathBuf>,
+
+    /// File to record fingerprints of compilations to skip unnecessary recompilation
+    pub compile_cache_file: Option<PathBuf>,
+
+    /// Compile even if the compile cache is up to date
+    pub force_compile: bool,
+
+    /// Used to find headers which the program depends on
+    pub expander: Option<ExpanderConfig>,
+
+    /// Run only the testcases whose names match any of the patterns (all if empty)
+    pub only: Vec<GlobPattern>,
+
+    /// Skip the testcases whose names match any of the patterns
+    pub skip: Vec<GlobPattern>,
+
+    /// Don't start the remaining testcases after the first failure
+    pub fail_fast: bool,
+
+    /// Profile of `test.command[]` to build and run the program with (release if `None`)
+    pub profile: Option<String>,
+}
+
+impl TestOptions {
+    pub fn is_testcase_selected(&self, name: &str) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|p| p.matches(name)))
+            && !self.skip.iter().any(|p| p.matches(name))
+    }
+
+    /// Load options declared in the workspace's test config file and the problem info.
+    pub fn from_workspace(workspace: &ProblemWorkspace, global_cfg: &Config) -> Result<Self> {
+        let cfg = WorkspaceTestConfig::from_toml_file_or_default(workspace.test_config_file())?;
+        let dir = workspace.dir();
+        let problem_info = workspace.load_problem_info().ok();
+        Ok(Self {
+            comparator: cfg.judge,
+            checker: cfg.checker.map(|path| dir.join(path)),
+            interactor: cfg.interactor.map(|path| dir.join(path)),
+            memory_limit_kb: problem_info
+                .as_ref()
+                .map(|info| info.memory_limit_kb as u64),
+            execution_time_limit: problem_info
+                .as_ref()
+                .map(|info| global_cfg.test.scale_time_limit(info.execution_time_limit)),
+            user_testcase_dir: Some(workspace.user_testcase_dir()),
+            compile_cache_file: Some(workspace.compile_cache_file()),
+            expander: Some(global_cfg.expander.clone()),
+            ..Default::default()
+        })
+    }
+}
+
+/// Warn if the execution time of an accepted run reaches this ratio of the time limit.
+const TIME_LIMIT_WARNING_RATIO: f64 = 0.8;
+
 pub async fn do_test_with_runner(
     runner: &TestRunner,
     testcase_dir: impl AsRef<Path>,
     cfg: &TestConfig,
+    opts: &TestOptions,
 ) -> Result<Vec<TestOutcome>> {
-    let testcases = FsTestcase::enumerate(&testcase_dir, &workspace::TestcaseFinder)
-        .context("Failed to find testcase")?;
+    let mut testcases = self::enumerate_testcases(testcase_dir.as_ref(), cfg, opts)?;
     if testcases.is_empty() {
         bail!(
             "No testcases is saved in {}",
             testcase_dir.as_ref().to_string_lossy()
         );
     }
+    testcases.retain(|t| opts.is_testcase_selected(t.name()));
+    if testcases.is_empty() {
+        bail!("No testcases match the filters (--only/--skip)");
+    }
 
-    if cfg.compile_before_run && runner.is_compile_cmd_defined() {
-        let cmd = runner.get_command().compile.as_ref().unwrap();
-        log::info!("Compile: {}", cmd);
-        runner.compile().await?;
+    self::compile_programs(&self::judge_programs(runner), cfg, opts).await?;
+    if let Err(e) = self::compile_programs(&[(runner, "program")], cfg, opts).await {
+        let Some(ce) = e.downcast_ref::<CompileError>() else {
+            return Err(e);
+        };
+        let results: Vec<_> = testcases
+            .iter()
+            .map(|t| TestOutcome {
+                testcase_name: t.name().to_owned(),
+                origin: t.origin(),
+                judge: JudgeCode::CE,
+                execution_time: Duration::ZERO,
+                groundtruth: String::new(),
+                output: ce.output.clone(),
+                checker_message: None,
+                comparator: None,
+                reason: None,
+                transcript: None,
+                resource_usage: None,
+                timing_stats: None,
+                omitted_lines: Default::default(),
+            })
+            .collect();
+        style::print_test_result_summary(&results);
+        return Ok(results);
     }
 
     let style = ProgressStyle::default_spinner();
 
-    let mut results = Vec::with_capacity(testcases.len());
     let mut bars = Vec::with_capacity(testcases.len());
     let progress_bar_container = MultiProgress::new();
 
@@ -278,33 +462,94 @@ pub async fn do_test_with_runner(
         let bar = progress_bar_container
             .add(ProgressBar::new(100))
             .with_style(style.clone())
-            .with_message(format!("Testcase {} ...", t.name()))
+            .with_message(format!(
+                "Testcase {} ...",
+                style::testcase_label(t.name(), t.origin())
+            ))
             .with_ticking();
         bars.push(bar);
     }
 
-    for (t, bar) in testcases.iter().zip(&bars) {
-        let res = runner
-            .run(
-                t,
-                cfg.stdout_capture_max_bytes,
-                cfg.stderr_capture_max_bytes,
-            )
-            .await?;
-        bar.lock().await.finish_with_message({
-            format!(
-                "Testcase {} ... {}{} [{}ms]",
-                t.name(),
-                style::judge_icon(res.judge),
-                " ".repeat(3 - res.judge.to_string().len()),
-                res.execution_time.as_millis(),
-            )
-            .cyan()
-            .to_string()
+    let jobs = opts.jobs.unwrap_or_else(|| cfg.parallelism_or_default());
+    let semaphore = Arc::new(Semaphore::new(jobs.max(1)));
+    let runner = Arc::new(runner.clone());
+    let repeat = opts.repeat.unwrap_or(1).max(1);
+    let time_limit = runner.get_exec_time_limit();
+    let failed = Arc::new(AtomicBool::new(false));
+    let fail_fast = opts.fail_fast;
+
+    // The tasks are aborted when the set is dropped (e.g. the run is canceled by `watch_test`).
+    let num_testcases = testcases.len();
+    let mut tasks = JoinSet::new();
+    for (i, (t, bar)) in testcases.into_iter().zip(bars).enumerate() {
+        let semaphore = semaphore.clone();
+        let runner = runner.clone();
+        let failed = failed.clone();
+        let (stdout_max, stderr_max) = (cfg.stdout_capture_max_bytes, cfg.stderr_capture_max_bytes);
+
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+            if fail_fast && failed.load(Ordering::SeqCst) {
+                bar.lock().await.finish_with_message(
+                    format!(
+                        "Testcase {} ... skipped",
+                        style::testcase_label(t.name(), t.origin())
+                    )
+                    .dimmed()
+                    .to_string(),
+                );
+                return Ok((i, None));
+            }
+            let res = self::run_repeatedly(&runner, &t, repeat, stdout_max, stderr_max).await?;
+            if res.judge.is_failure() {
+                failed.store(true, Ordering::SeqCst);
+            }
+            bar.lock().await.finish_with_message({
+                let mut msg = format!(
+                    "Testcase {} ... {}{} [{}]",
+                    style::testcase_label(t.name(), t.origin()),
+                    style::judge_icon(res.judge),
+                    " ".repeat(3 - res.judge.to_string().len()),
+                    style::resource_usage_summary(&res),
+                )
+                .cyan()
+                .to_string();
+                if let Some(stats) = &res.timing_stats {
+                    msg += &format!(" ({})", style::timing_stats_summary(stats))
+                        .cyan()
+                        .to_string();
+                }
+                if res.judge == JudgeCode::AC
+                    && res.execution_time >= time_limit.mul_f64(TIME_LIMIT_WARNING_RATIO)
+                {
+                    msg += &format!(
+                        " ⚠ {}% of the time limit",
+                        res.execution_time.as_millis() * 100 / time_limit.as_millis().max(1)
+                    )
+                    .bright_yellow()
+                    .to_string();
+                }
+                msg
+            });
+            Ok::<_, Error>((i, Some(res)))
         });
-        results.push(res);
     }
+
+    // Sort in the order of testcases so that the order of results is stable.
+    let mut outcomes = vec![None; num_testcases];
+    while let Some(task) = tasks.join_next().await {
+        let (i, res) = task??;
+        outcomes[i] = res;
+    }
+    let num_skipped = outcomes.iter().filter(|res| res.is_none()).count();
+    let results: Vec<_> = outcomes.into_iter().flatten().collect();
     print!("\n");
+    if num_skipped > 0 {
+        log::info!(
+            "Skipped {} testcases after the first failure (--fail-fast)",
+            num_skipped
+        );
+    }
 
     results
         .iter()
@@ -315,33 +560,639 @@ pub async fn do_test_with_runner(
     Ok(results)
 }
 
+/// Testcases in the testcase dir and the user testcase dir, without applying the filters.
+fn enumerate_testcases(
+    testcase_dir: &Path,
+    cfg: &TestConfig,
+    opts: &TestOptions,
+) -> Result<Vec<FsTestcase>> {
+    let mut sources = vec![(testcase_dir.to_owned(), TestcaseOrigin::Sample)];
+    if let Some(dir) = &opts.user_testcase_dir {
+        sources.push((dir.to_owned(), TestcaseOrigin::User));
+    }
+    let finder = workspace::PatternTestcaseFinder::new(&cfg.testcase_layout)
+        .context("Invalid `test.testcase_layout[]`")?;
+    FsTestcase::enumerate_merged(&sources, &finder).context("Failed to find testcase")
+}
+
+/// Run the reference solution against the input-only testcases, and save its outputs as their
+/// expected outputs. Returns the paths of the saved files.
+pub async fn record_groundtruths(
+    reference_file: impl AsRef<Path>,
+    testcase_dir: impl AsRef<Path>,
+    cfg: &TestConfig,
+    opts: &TestOptions,
+) -> Result<Vec<PathBuf>> {
+    let testcases: Vec<_> = self::enumerate_testcases(testcase_dir.as_ref(), cfg, opts)?
+        .into_iter()
+        .filter(|t| !t.has_groundtruth() && opts.is_testcase_selected(t.name()))
+        .collect();
+    if testcases.is_empty() {
+        log::info!("No testcases to record (all of them have the expected outputs)");
+        return Ok(Vec::new());
+    }
+
+    let reference = TestRunner::new(self::find_test_cmd_for_program(&reference_file, cfg, None)?)
+        .shell(cfg.shell.to_owned())
+        .program_file(&reference_file)?;
+    let reference = self::apply_time_limit(reference, opts);
+    self::compile_programs(&[(&reference, "reference")], cfg, opts).await?;
+
+    let mut recorded = Vec::with_capacity(testcases.len());
+    for t in &testcases {
+        let input_file = t
+            .input_file_path()
+            .expect("FsTestcase should have the input file");
+        let output_file = t
+            .groundtruth_file_path()
+            .expect("FsTestcase should have the groundtruth file path");
+        let input = tokio::fs::read(input_file)
+            .await
+            .with_context(|| format!("Cannot read {:?}", input_file))?;
+        let Some(output) = reference
+            .exec_within_time_limit(&[] as &[&str], &input)
+            .await?
+        else {
+            bail!(
+                "Reference solution exceeded the time limit of {}ms on testcase {}",
+                reference.get_exec_time_limit().as_millis(),
+                style::testcase_label(t.name(), t.origin())
+            );
+        };
+        if !output.status.success() {
+            bail!(
+                "Reference solution failed on testcase {} ({})\n{}",
+                style::testcase_label(t.name(), t.origin()),
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        fsutil::write_with_mkdir(output_file, &output.stdout)?;
+        log::info!("Recorded: {}", output_file.to_string_lossy());
+        recorded.push(output_file.to_owned());
+    }
+    Ok(recorded)
+}
+
+/// Run the testcase `repeat` times (stopping at the first failure), and returns the outcome of
+/// the failed or the slowest run with statistics of execution times.
+async fn run_repeatedly<'t, T: AsyncTestcase<'t>>(
+    runner: &TestRunner,
+    testcase: &'t T,
+    repeat: usize,
+    stdout_capture_max_bytes: usize,
+    stderr_capture_max_bytes: usize,
+) -> Result<TestOutcome> {
+    let mut res = runner
+        .run(testcase, stdout_capture_max_bytes, stderr_capture_max_bytes)
+        .await?;
+    if repeat <= 1 {
+        return Ok(res);
+    }
+
+    let mut execution_times = vec![res.execution_time];
+    while execution_times.len() < repeat && !res.judge.is_failure() {
+        let next = runner
+            .run(testcase, stdout_capture_max_bytes, stderr_capture_max_bytes)
+            .await?;
+        execution_times.push(next.execution_time);
+        if next.judge.is_failure() || next.execution_time > res.execution_time {
+            res = next;
+        }
+    }
+    res.timing_stats = TimingStats::from_durations(&execution_times);
+    Ok(res)
+}
+
 pub async fn do_test_with_command(
     cmd: TestCommand,
     testcase_dir: impl AsRef<Path>,
     cfg: &TestConfig,
+    opts: &TestOptions,
 ) -> Result<Vec<TestOutcome>> {
-    let runner = TestRunner::new(cmd).shell(cfg.shell.to_owned());
-    self::do_test_with_runner(&runner, testcase_dir, cfg).await
+    let runner = TestRunner::new(cmd)
+        .shell(cfg.shell.to_owned())
+        .comparator(opts.comparator.unwrap_or(cfg.judge))
+        .memory_limit_kb(opts.memory_limit_kb)
+        .sandbox(self::sandbox_for(cfg, opts));
+    let runner = self::apply_time_limit(runner, opts);
+    let runner = self::attach_judge_programs(runner, cfg, opts)?;
+    self::do_test_with_runner(&runner, testcase_dir, cfg, opts).await
 }
 
 pub async fn do_test(
     program_file: impl AsRef<Path>,
     testcase_dir: impl AsRef<Path>,
     cfg: &TestConfig,
+    opts: &TestOptions,
 ) -> Result<Vec<TestOutcome>> {
+    let runner = self::build_test_runner(program_file, cfg, opts)?;
+    self::do_test_with_runner(&runner, testcase_dir, cfg, opts).await
+}
+
+/// Compile and run the program without judging, feeding `input_file` (or the terminal) to stdin.
+pub async fn run_program(
+    program_file: impl AsRef<Path>,
+    input_file: Option<&Path>,
+    cfg: &TestConfig,
+    opts: &TestOptions,
+) -> Result<RunOutcome> {
+    let runner = self::build_test_runner(program_file, cfg, opts)?;
+    self::compile_programs(&[(&runner, "program")], cfg, opts).await?;
+
+    log::info!("Running: {}", runner.get_command().run);
+    let stdin = match input_file {
+        Some(path) => std::fs::File::open(path)
+            .with_context(|| format!("Failed to open input file: {:?}", path))?
+            .into(),
+        None => {
+            if std::io::stdin().is_terminal() {
+                log::info!("Reading stdin from the terminal (Ctrl-D to end)");
+            }
+            Stdio::inherit()
+        }
+    };
+
+    let res = runner.run_without_judge(stdin).await?;
+    style::print_run_outcome(&res);
+    Ok(res)
+}
+
+/// Changes of the watched files within this duration are coalesced into one re-run.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Run the tests whenever the program file, its local headers or the testcases are changed.
+/// The in-flight run is canceled on a change. Returns on Ctrl-C, killing the running solutions.
+pub async fn watch_test(
+    program_file: impl AsRef<Path>,
+    testcase_dir: impl AsRef<Path>,
+    cfg: &TestConfig,
+    opts: &TestOptions,
+) -> Result<()> {
+    let program_file = program_file.as_ref();
+    let testcase_dir = testcase_dir.as_ref();
+
+    loop {
+        // Rebuild the watcher for each run, since included headers may be changed.
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let _watcher = self::watch_test_inputs(program_file, testcase_dir, opts, tx)?;
+
+        style::clear_screen();
+        // Dropping the run cancels it, killing the running solutions.
+        tokio::select! {
+            res = self::do_test(program_file, testcase_dir, cfg, opts) => {
+                if let Err(e) = res {
+                    log::error!("{:#}", e);
+                }
+                log::info!("Watching for changes ... (Ctrl-C to quit)");
+                tokio::select! {
+                    _ = rx.recv() => {}
+                    _ = tokio::signal::ctrl_c() => return Ok(()),
+                }
+            }
+            _ = rx.recv() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+
+        while let Ok(Some(())) = tokio::time::timeout(WATCH_DEBOUNCE, rx.recv()).await {}
+    }
+}
+
+fn watch_test_inputs(
+    program_file: &Path,
+    testcase_dir: &Path,
+    opts: &TestOptions,
+    tx: tokio::sync::mpsc::UnboundedSender<()>,
+) -> Result<notify::RecommendedWatcher> {
+    use notify::{RecursiveMode, Watcher as _};
+
+    let mut files = vec![program_file.to_owned()];
+    if let Some(expander_cfg) = &opts.expander {
+        files.extend(self::dependent_header_paths(program_file, expander_cfg));
+    }
+    let files: HashSet<PathBuf> = files.iter().filter_map(|f| f.canonicalize().ok()).collect();
+    let dirs: Vec<PathBuf> = [Some(testcase_dir), opts.user_testcase_dir.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .collect();
+
+    // Files are watched via their parent dirs, since editors often replace the file on save.
+    // Events of the other files in the dirs (e.g. compiled binaries) are ignored.
+    let (watched_files, watched_dirs) = (files.clone(), dirs.clone());
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else {
+            return;
+        };
+        if event.kind.is_access() {
+            return;
+        }
+        let is_relevant = event.paths.iter().any(|path| {
+            watched_files.contains(path) || watched_dirs.iter().any(|dir| path.starts_with(dir))
+        });
+        if is_relevant {
+            let _ = tx.send(());
+        }
+    })
+    .context("Failed to create file watcher")?;
+
+    let parents: HashSet<&Path> = files.iter().filter_map(|f| f.parent()).collect();
+    for dir in parents {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {:?}", dir))?;
+    }
+    for dir in &dirs {
+        watcher
+            .watch(dir, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {:?}", dir))?;
+    }
+    Ok(watcher)
+}
+
+/// Build the runner for the program file, configured by `test.command[]` and `opts`.
+pub fn build_test_runner(
+    program_file: impl AsRef<Path>,
+    cfg: &TestConfig,
+    opts: &TestOptions,
+) -> Result<TestRunner> {
     let filename = program_file.as_ref().file_name().unwrap().to_string_lossy();
-    let cmd = cfg.find_test_cmd_for_filename(&filename).with_context(|| {
-            format!(
-                "Unconfigured test command for filename '{}' (No entry matched glob in `test.command[]`)",
-                filename
-            )
-        })?;
+    let cmd = self::find_test_cmd_for_program(&program_file, cfg, opts.profile.as_deref())?;
+
+    let comparator = opts
+        .comparator
+        .unwrap_or_else(|| cfg.find_comparator_for_filename(&filename));
 
     let runner = TestRunner::new(cmd)
         .shell(cfg.shell.to_owned())
+        .comparator(comparator)
+        .memory_limit_kb(opts.memory_limit_kb)
+        .sandbox(self::sandbox_for(cfg, opts))
         .program_file(&program_file)?;
+    let runner = self::apply_time_limit(runner, opts);
+    self::attach_judge_programs(runner, cfg, opts)
+}
+
+fn sandbox_for(cfg: &TestConfig, opts: &TestOptions) -> Option<Sandbox> {
+    (opts.sandbox || cfg.sandbox.enabled).then(|| cfg.sandbox.to_sandbox())
+}
+
+fn apply_time_limit(runner: TestRunner, opts: &TestOptions) -> TestRunner {
+    match opts.execution_time_limit {
+        Some(limit) => runner.execution_time_limit(limit),
+        None => runner,
+    }
+}
+
+/// Attach the checker and the interactor specified in `opts` to the runner.
+fn attach_judge_programs(
+    mut runner: TestRunner,
+    cfg: &TestConfig,
+    opts: &TestOptions,
+) -> Result<TestRunner> {
+    if let Some(checker_file) = &opts.checker {
+        let cmd = self::find_test_cmd_for_program(checker_file, cfg, None)?;
+        let checker = Checker::new(cmd)
+            .shell(cfg.shell.to_owned())
+            .program_file(checker_file)?;
+        runner = runner.checker(checker);
+    }
+    if let Some(interactor_file) = &opts.interactor {
+        let cmd = self::find_test_cmd_for_program(interactor_file, cfg, None)?;
+        let interactor = Interactor::new(cmd)
+            .shell(cfg.shell.to_owned())
+            .program_file(interactor_file)?;
+        runner = runner.interactor(interactor);
+    }
+    Ok(runner)
+}
+
+fn find_test_cmd_for_program(
+    program_file: impl AsRef<Path>,
+    cfg: &TestConfig,
+    profile: Option<&str>,
+) -> Result<TestCommand> {
+    let filename = program_file.as_ref().file_name().unwrap().to_string_lossy();
+    cfg.find_test_cmd_for_filename(&filename, profile)
+}
+
+/// Options for `do_stress_test()`
+#[derive(Debug, Clone)]
+pub struct StressOptions {
+    /// Seed passed to the generator at the first iteration (incremented for each iteration)
+    pub seed: u64,
+    pub max_iterations: usize,
+
+    /// Time limit of the naive solution and the generator
+    pub naive_time_limit: Duration,
+}
+
+impl StressOptions {
+    pub const DEFAULT_NAIVE_TIME_LIMIT: Duration = Duration::from_secs(10);
+}
+
+/// Input on which the program disagrees with the naive solution.
+#[derive(Debug, Clone)]
+pub struct StressCounterexample {
+    pub seed: u64,
+    pub outcome: TestOutcome,
+    pub input_file: PathBuf,
+    pub output_file: PathBuf,
+}
+
+/// Run the program and the naive solution against inputs made by the generator until they disagree.
+/// The generator is invoked as `<run> <seed>` and the counterexample is saved into the workspace.
+pub async fn do_stress_test(
+    program_file: impl AsRef<Path>,
+    naive_file: impl AsRef<Path>,
+    generator_file: impl AsRef<Path>,
+    workspace: &ProblemWorkspace,
+    cfg: &TestConfig,
+    test_opts: &TestOptions,
+    stress_opts: &StressOptions,
+) -> Result<Option<StressCounterexample>> {
+    ensure!(
+        test_opts.interactor.is_none(),
+        "Stress test is not supported for interactive problems"
+    );
+
+    let runner = self::build_test_runner(program_file, cfg, test_opts)?;
+    let naive = TestRunner::new(self::find_test_cmd_for_program(&naive_file, cfg, None)?)
+        .shell(cfg.shell.to_owned())
+        .execution_time_limit(stress_opts.naive_time_limit)
+        .program_file(&naive_file)?;
+    let generator = TestRunner::new(self::find_test_cmd_for_program(&generator_file, cfg, None)?)
+        .shell(cfg.shell.to_owned())
+        .execution_time_limit(stress_opts.naive_time_limit)
+        .program_file(&generator_file)?;
+    let time_limit_ms = stress_opts.naive_time_limit.as_millis();
+
+    let mut programs = vec![
+        (&runner, "program"),
+        (&naive, "naive"),
+        (&generator, "generator"),
+    ];
+    programs.extend(self::judge_programs(&runner));
+    self::compile_programs(&programs, cfg, test_opts).await?;
+
+    let spinner = ProgressBar::new(1)
+        .with_style(ProgressStyle::with_template(" {spinner} {wide_msg}").unwrap())
+        .with_ticking();
+
+    for i in 0..stress_opts.max_iterations {
+        let seed = stress_opts.seed.wrapping_add(i as u64);
+        spinner
+            .lock()
+            .await
+            .set_message(format!("Stress test #{} (seed={}) ...", i + 1, seed));
+
+        let Some(generated) = generator
+            .exec_within_time_limit(&[seed.to_string()], b"")
+            .await?
+        else {
+            spinner.lock().await.finish_and_clear();
+            bail!(
+                "Generator exceeded the time limit of {}ms (seed={})",
+                time_limit_ms,
+                seed
+            );
+        };
+        if !generated.status.success() {
+            spinner.lock().await.finish_and_clear();
+            bail!(
+                "Generator failed (seed={}, {})\n{}",
+                seed,
+                generated.status,
+                String::from_utf8_lossy(&generated.stderr)
+            );
+        }
+        let input = generated.stdout;
+
+        let Some(expected) = naive.exec_within_time_limit(&[] as &[&str], &input).await? else {
+            spinner.lock().await.finish_and_clear();
+            let shown = input.len().min(cfg.stdout_capture_max_bytes);
+            bail!(
+                "Naive solution exceeded the time limit of {}ms (seed={}) on the input:\n{}{}",
+                time_limit_ms,
+                seed,
+                String::from_utf8_lossy(&input[..shown]).trim_end(),
+                match input.len() - shown {
+                    0 => String::new(),
+                    n => format!("\n... {} more bytes truncated", n),
+                }
+            );
+        };
+        if !expected.status.success() {
+            spinner.lock().await.finish_and_clear();
+            bail!(
+                "Naive solution failed (seed={}, {})\n{}",
+                seed,
+                expected.status,
+                String::from_utf8_lossy(&expected.stderr)
+            );
+        }
+        let answer = expected.stdout;
+
+        let t = OnMemoryTestcase::<Vec<u8>>::new(format!("seed={}", seed), input, answer);
+        let outcome = runner
+            .run(
+                &t,
+                cfg.stdout_capture_max_bytes,
+                cfg.stderr_capture_max_bytes,
+            )
+            .await?;
+        if outcome.judge == JudgeCode::AC {
+            continue;
+        }
+
+        spinner.lock().await.finish_and_clear();
+        let name = workspace.unused_testcase_name("stress");
+        let (input_file, output_file) = workspace
+            .save_user_testcase(&name, &t.input, &t.groundtruth)
+            .context("Failed to save the counterexample")?;
+        style::print_test_result_detail(&outcome);
+        return Ok(Some(StressCounterexample {
+            seed,
+            outcome,
+            input_file,
+            output_file,
+        }));
+    }
+
+    spinner.lock().await.finish_and_clear();
+    Ok(None)
+}
+
+/// Checker and interactor attached to the runner.
+fn judge_programs(runner: &TestRunner) -> Vec<(&TestRunner, &'static str)> {
+    let mut programs = Vec::new();
+    if let Some(checker) = runner.get_checker() {
+        programs.push((checker.get_runner(), "checker"));
+    }
+    if let Some(interactor) = runner.get_interactor() {
+        programs.push((interactor.get_runner(), "interactor"));
+    }
+    programs
+}
+
+/// Compile the programs if `test.compile_before_run` is enabled.
+/// Compilation is skipped if neither the sources nor the compile command has changed since the last
+/// one, unless `opts.force_compile` is set.
+///
+/// On a compile error, the diagnostics are printed and [`CompileError`] is returned.
+async fn compile_programs(
+    programs: &[(&TestRunner, &str)],
+    cfg: &TestConfig,
+    opts: &TestOptions,
+) -> Result<()> {
+    if !cfg.compile_before_run {
+        return Ok(());
+    }
+
+    let cache = opts.compile_cache_file.as_ref().map(CompileCache::new);
+    for &(runner, role) in programs {
+        let Some(cmd) = &runner.get_command().compile else {
+            continue;
+        };
+        // Profiles may share the executable, so the entry is keyed on the run command.
+        let cache_key = &runner.get_command().run;
+        let fingerprint = runner
+            .get_program_file()
+            .and_then(|file| self::compile_fingerprint(runner, file, opts.expander.as_ref()));
+
+        // Compiled every time if the product is unknown, since it cannot be checked.
+        let product = runner.get_command().product_file();
+        if let (Some(cache), Some(fingerprint), Some(product), false) =
+            (&cache, &fingerprint, &product, opts.force_compile)
+        {
+            if cache.is_up_to_date(cache_key, fingerprint, product) {
+                log::info!("Compile {}: skipped (up to date)", role);
+                continue;
+            }
+        }
+
+        log::info!("Compile {}: {}", role, cmd);
+        match runner.compile().await {
+            Ok(output) => {
+                // e.g. warnings
+                eprint!("{}{}", output.stdout, output.stderr);
+            }
+            Err(e) => {
+                if let Some(ce) = e.downcast_ref::<CompileError>() {
+                    style::print_compile_error(role, ce);
+                }
+                return Err(e);
+            }
+        }
+
+        if let (Some(cache), Some(fingerprint), Some(product)) = (&cache, fingerprint, &product) {
+            cache
+                .update(cache_key, fingerprint, product)
+                .unwrap_or_else(|e| log::warn!("Failed to update compile cache: {:#}", e));
+        }
+    }
+    Ok(())
+}
+
+/// Hash of the compile command, the program file and the headers it depends on.
+fn compile_fingerprint(
+    runner: &TestRunner,
+    program_file: &Path,
+    expander_cfg: Option<&ExpanderConfig>,
+) -> Option<String> {
+    let mut files = vec![program_file.to_owned()];
+    if let Some(cfg) = expander_cfg {
+        files.extend(self::dependent_header_paths(program_file, cfg));
+    }
+    compile_cache::fingerprint(runner.get_shell(), runner.get_command(), &files)
+        .map_err(|e| {
+            log::warn!(
+                "Failed to compute fingerprint of {:?}: {:#}",
+                program_file,
+                e
+            )
+        })
+        .ok()
+}
+
+/// Returns paths of the header files which the C/C++ program file includes.
+fn dependent_header_paths(program_file: &Path, cfg: &ExpanderConfig) -> Vec<PathBuf> {
+    let Some(ext) = program_file.extension().and_then(OsStr::to_str) else {
+        return Vec::new();
+    };
+    if !["c", "cpp", "cc", "cxx", "h", "hpp"].contains(&ext) {
+        return Vec::new();
+    }
+    let (Ok(abs_filepath), Ok(content)) = (
+        program_file.canonicalize(),
+        fsutil::read_to_string(program_file),
+    ) else {
+        return Vec::new();
+    };
+
+    let header_search_dirs = cfg.resolved_header_search_dirs();
+    let mut expander = kpr_expander::cpp::Expander::default()
+        .header_serch_dirs(&header_search_dirs)
+        .expansion_targets(&cfg.cpp.expansion_targets)
+        .expansion_ignores(&cfg.cpp.expansion_ignores);
+    if let Err(e) = expander.emit(abs_filepath, content) {
+        log::debug!("Failed to resolve headers of {:?}: {}", program_file, e);
+    }
+    expander.get_dependent_header_paths()
+}
+
+/// Minimize the failing input, keeping the verdict of the program against the naive solution.
+/// Candidates on which the naive solution or the validator exceeds `naive_time_limit` are rejected.
+pub async fn do_shrink(
+    input: String,
+    program_file: impl AsRef<Path>,
+    naive_file: impl AsRef<Path>,
+    validator_file: Option<impl AsRef<Path>>,
+    naive_time_limit: Duration,
+    cfg: &TestConfig,
+    test_opts: &TestOptions,
+) -> Result<ShrinkResult> {
+    ensure!(
+        test_opts.interactor.is_none(),
+        "Shrinking input is not supported for interactive problems"
+    );
+
+    let runner = self::build_test_runner(program_file, cfg, test_opts)?;
+    let naive = TestRunner::new(self::find_test_cmd_for_program(&naive_file, cfg, None)?)
+        .shell(cfg.shell.to_owned())
+        .execution_time_limit(naive_time_limit)
+        .program_file(&naive_file)?;
+    let validator = match &validator_file {
+        Some(file) => Some(
+            TestRunner::new(self::find_test_cmd_for_program(file, cfg, None)?)
+                .shell(cfg.shell.to_owned())
+                .execution_time_limit(naive_time_limit)
+                .program_file(file)?,
+        ),
+        None => None,
+    };
+
+    let mut programs = vec![(&runner, "program"), (&naive, "naive")];
+    if let Some(validator) = &validator {
+        programs.push((validator, "validator"));
+    }
+    programs.extend(self::judge_programs(&runner));
+    self::compile_programs(&programs, cfg, test_opts).await?;
+
+    let spinner = ProgressBar::new(1)
+        .with_style(ProgressStyle::with_template(" {spinner} {wide_msg}").unwrap())
+        .with_message(format!("Shrinking the input ({} bytes) ...", input.len()))
+        .with_ticking();
+
+    let mut shrinker = Shrinker::new(&runner, &naive)
+        .capture_max_bytes(cfg.stdout_capture_max_bytes, cfg.stderr_capture_max_bytes);
+    if let Some(validator) = validator {
+        shrinker = shrinker.validator(ProgramValidator::new(validator));
+    }
+    let res = shrinker.shrink(input).await;
 
-    self::do_test_with_runner(&runner, testcase_dir, cfg).await
+    spinner.lock().await.finish_and_clear();
+    res
 }
 
 pub async fn submit(
@@ -410,18 +1261,7 @@ pub fn expand_source_code(
             let content = ::std::fs::read_to_string(&abs_filepath).map_err(|_| {
                 ExpanderError::FileNotFound(format!("Cannot read file {:?}", program_file))
             })?;
-            let header_search_dirs = cfg
-                .cpp
-                .header_search_dirs
-                .iter()
-                .map(|dir| {
-                    if dir.is_absolute() {
-                        fsutil::normalize_path(dir)
-                    } else {
-                        fsutil::normalize_path(cfg.source_config_dir.join(dir))
-                    }
-                })
-                .collect::<Vec<_>>();
+            let header_search_dirs = cfg.resolved_header_search_dirs();
 
             kpr_expander::cpp::Expander::default()
                 .header_serch_dirs(&header_search_dirs)
@@ -1,5 +1,7 @@
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
+use std::time::Duration;
 
 use ::anyhow::Context as _;
 use ::kpr_webclient::Platform;
@@ -7,13 +9,13 @@ use ::rust_embed::RustEmbed;
 use ::serdable::GlobPattern;
 use ::serde::Deserialize;
 
-use crate::testing::runner::TestCommand;
+use crate::testing::{comparator::Comparator, runner::TestCommand, sandbox::Sandbox};
 
 pub fn authtoken_filename(platform: Platform) -> String {
     format!("{}-auth.json", platform.lowercase())
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct Config {
     #[serde(skip)]
     pub source_config_dir: PathBuf,
@@ -32,16 +34,56 @@ pub struct RepoConfig {
     pub workspace_template: PathBuf,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct TestConfig {
     pub shell: PathBuf,
     pub include: GlobPattern,
     pub compile_before_run: bool,
     pub stdout_capture_max_bytes: usize,
     pub stderr_capture_max_bytes: usize,
+    #[serde(default)]
+    pub judge: Comparator,
+    /// Max number of testcases to run concurrently
+    #[serde(default)]
+    pub parallelism: Option<usize>,
+    /// Safety factor multiplied to the time limit of the problem
+    #[serde(default)]
+    pub time_limit_factor: Option<f64>,
+    #[serde(default)]
+    pub sandbox: SandboxConfig,
+    /// Layouts of testcase files recognized in addition to `in_{name}.txt` and `out_{name}.txt`
+    #[serde(default)]
+    pub testcase_layout: Vec<TestcaseLayoutConfig>,
     pub command: Vec<TestCommandConfig>,
 }
 
+/// Paths of the input and output files relative to the testcase dir, containing `{name}`
+/// in the file name (e.g. `{name}.in`, `in/{name}.txt`).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TestcaseLayoutConfig {
+    pub input: String,
+    pub output: String,
+}
+
+/// Resource limits and isolation of solutions. Omitted limits are unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct SandboxConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub stack_limit_kb: Option<u64>,
+    #[serde(default)]
+    pub address_space_limit_kb: Option<u64>,
+    /// Defaults to 256 MiB
+    #[serde(default)]
+    pub file_size_limit_kb: Option<u64>,
+    /// Defaults to 4096
+    #[serde(default)]
+    pub max_processes: Option<u64>,
+    #[serde(default)]
+    pub allow_network: bool,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub struct ExpanderConfig {
     #[serde(skip)]
@@ -61,6 +103,42 @@ pub struct TestCommandConfig {
     pub pattern: GlobPattern,
     pub compile: Option<String>,
     pub run: String,
+    #[serde(default)]
+    pub judge: Option<Comparator>,
+    /// Named variants of `compile` and `run` selected by `kpr test --profile <NAME>`
+    #[serde(default)]
+    pub profiles: BTreeMap<String, TestCommandProfile>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Defaults to `.kpr/build` in the dir of the program file
+    #[serde(default)]
+    pub build_dir: Option<PathBuf>,
+}
+
+/// Overrides of `compile` and `run` of a `test.command[]` entry. Omitted ones are inherited.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct TestCommandProfile {
+    #[serde(default)]
+    pub compile: Option<String>,
+    #[serde(default)]
+    pub run: Option<String>,
+}
+
+/// Per-workspace test settings, placed as `kpr-test.toml` in a problem workspace.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct WorkspaceTestConfig {
+    #[serde(default)]
+    pub judge: Option<Comparator>,
+
+    /// Source file of the special judge program (relative to the workspace dir)
+    #[serde(default)]
+    pub checker: Option<PathBuf>,
+
+    /// Source file of the interactor for interactive problems (relative to the workspace dir)
+    #[serde(default)]
+    pub interactor: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
@@ -81,6 +159,24 @@ pub struct SubmissionLangConfigEntry {
     pub lang: String,
 }
 
+impl SandboxConfig {
+    const DEFAULT_FILE_SIZE_LIMIT_KB: u64 = 256 * 1024;
+    const DEFAULT_MAX_PROCESSES: u64 = 4096;
+
+    pub fn to_sandbox(&self) -> Sandbox {
+        Sandbox {
+            stack_limit_kb: self.stack_limit_kb,
+            address_space_limit_kb: self.address_space_limit_kb,
+            file_size_limit_kb: Some(
+                self.file_size_limit_kb
+                    .unwrap_or(Self::DEFAULT_FILE_SIZE_LIMIT_KB),
+            ),
+            max_processes: Some(self.max_processes.unwrap_or(Self::DEFAULT_MAX_PROCESSES)),
+            allow_network: self.allow_network,
+        }
+    }
+}
+
 #[derive(RustEmbed)]
 #[folder = "examples/assets/"]
 struct Asset;
@@ -133,15 +229,132 @@ impl Config {
     }
 }
 
+impl TestCommandConfig {
+    /// Profile used when none is specified, e.g. by `kpr submit --test`.
+    /// It's the entry's own `compile` and `run` unless `profiles.release` is declared.
+    pub const RELEASE_PROFILE: &str = "release";
+
+    pub const DEFAULT_BUILD_DIR: &str = ".kpr/build";
+
+    pub fn to_test_cmd(&self, profile: Option<&str>) -> anyhow::Result<TestCommand> {
+        let name = profile.unwrap_or(Self::RELEASE_PROFILE);
+        let overrides = match self.profiles.get(name) {
+            Some(overrides) => overrides.clone(),
+            None if name == Self::RELEASE_PROFILE => TestCommandProfile::default(),
+            None => anyhow::bail!(
+                "Unknown profile '{}' for pattern '{}' (Available: {})",
+                name,
+                self.pattern.as_str(),
+                self.profile_names().collect::<Vec<_>>().join(", ")
+            ),
+        };
+        let mut cmd = TestCommand {
+            compile: overrides.compile.or_else(|| self.compile.to_owned()),
+            run: overrides.run.unwrap_or_else(|| self.run.to_owned()),
+            env: self.env.clone(),
+            cwd: self.cwd.clone(),
+            build_dir: None,
+        };
+        // The dir is made on compilation only if it's used.
+        if cmd.refers_to_var("buildDir") {
+            cmd.build_dir = Some(self.build_dir_or_default().to_owned());
+        }
+        Ok(cmd)
+    }
+
+    pub fn build_dir_or_default(&self) -> &Path {
+        self.build_dir
+            .as_deref()
+            .unwrap_or(Path::new(Self::DEFAULT_BUILD_DIR))
+    }
+
+    fn profile_names(&self) -> impl Iterator<Item = &str> {
+        let implicit_release =
+            (!self.profiles.contains_key(Self::RELEASE_PROFILE)).then_some(Self::RELEASE_PROFILE);
+        implicit_release
+            .into_iter()
+            .chain(self.profiles.keys().map(String::as_str))
+    }
+}
+
 impl TestConfig {
-    pub fn find_test_cmd_for_filename(&self, filename: impl AsRef<str>) -> Option<TestCommand> {
+    /// Returns the command of the first `test.command[]` entry matching the filename.
+    /// The release profile is used if `profile` is `None`.
+    pub fn find_test_cmd_for_filename(
+        &self,
+        filename: impl AsRef<str>,
+        profile: Option<&str>,
+    ) -> anyhow::Result<TestCommand> {
+        let filename = filename.as_ref();
+        self.find_command_entry(filename)
+            .with_context(|| {
+                format!(
+                    "Unconfigured test command for filename '{}' (No entry matched glob in `test.command[]`)",
+                    filename
+                )
+            })?
+            .to_test_cmd(profile)
+    }
+
+    fn find_command_entry(&self, filename: impl AsRef<str>) -> Option<&TestCommandConfig> {
         self.command
             .iter()
             .find(|entry| entry.pattern.matches(filename.as_ref()))
-            .map(|entry| TestCommand {
-                compile: entry.compile.to_owned(),
-                run: entry.run.to_owned(),
+    }
+
+    /// Returns `test.parallelism` if configured, otherwise (number of physical CPU cores) - 1
+    /// so that concurrent runs hardly skew the measured execution time.
+    pub fn parallelism_or_default(&self) -> usize {
+        self.parallelism
+            .unwrap_or_else(|| num_cpus::get_physical().saturating_sub(1))
+            .max(1)
+    }
+
+    /// Returns the time limit of the problem multiplied by `test.time_limit_factor` (default: 1.0).
+    pub fn scale_time_limit(&self, problem_time_limit: Duration) -> Duration {
+        match self.time_limit_factor {
+            Some(factor) if factor.is_finite() && factor > 0.0 => {
+                problem_time_limit.mul_f64(factor)
+            }
+            _ => problem_time_limit,
+        }
+    }
+
+    /// Returns the comparator of the matched `test.command[]` entry, or `test.judge` as fallback.
+    pub fn find_comparator_for_filename(&self, filename: impl AsRef<str>) -> Comparator {
+        self.find_command_entry(filename)
+            .and_then(|entry| entry.judge)
+            .unwrap_or(self.judge)
+    }
+}
+
+impl ExpanderConfig {
+    /// Returns `expander.cpp.header_search_dirs` resolving relative paths from the config dir.
+    pub fn resolved_header_search_dirs(&self) -> Vec<PathBuf> {
+        self.cpp
+            .header_search_dirs
+            .iter()
+            .map(|dir| {
+                if dir.is_absolute() {
+                    fsutil::normalize_path(dir)
+                } else {
+                    fsutil::normalize_path(self.source_config_dir.join(dir))
+                }
             })
+            .collect()
+    }
+}
+
+impl WorkspaceTestConfig {
+    /// Returns the default config if the file does not exist.
+    pub fn from_toml_file_or_default(filepath: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let filepath = filepath.as_ref();
+        if !filepath.exists() {
+            return Ok(Self::default());
+        }
+        let toml = fsutil::read_to_string(filepath).context("Cannot read a file")?;
+        toml::from_str(&toml)
+            .with_context(|| format!("Invalid workspace test config TOML: {:?}", filepath))
     }
 }
 
@@ -197,8 +410,24 @@ mod test {
         assert_eq!(test.shell, Path::new("/bin/sh"));
         assert_eq!(test.include, GlobPattern::parse("[mM]ain.*").unwrap());
         assert_eq!(test.compile_before_run, true);
+        assert_eq!(test.judge, Comparator::Exact);
+        assert_eq!(test.parallelism, None);
+        assert_eq!(test.time_limit_factor, None);
+        assert!(test.testcase_layout.is_empty());
         assert_eq!(test.command.len(), 3);
 
+        let cpp = &test.command[0];
+        let release = cpp.to_test_cmd(None).unwrap();
+        let debug = cpp.to_test_cmd(Some("debug")).unwrap();
+        assert_eq!(release, cpp.to_test_cmd(Some("release")).unwrap());
+        assert_ne!(debug.run, release.run);
+        assert!(debug.compile.unwrap().contains("-fsanitize=address"));
+        assert!(cpp.to_test_cmd(Some("fast")).is_err());
+        assert_eq!(release.build_dir.as_deref(), Some(Path::new(".kpr/build")));
+        assert!(release.run.contains("#{buildDir}"));
+        let python = test.command[2].to_test_cmd(None).unwrap();
+        assert_eq!(python.build_dir, None);
+
         assert_eq!(submit.run_test, true);
         assert_eq!(submit.apply_expander, true);
         assert_eq!(submit.lang.atcoder.len(), 3);
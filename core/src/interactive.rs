@@ -5,7 +5,7 @@ use indicatif::ProgressBar;
 use kpr_webclient::{CredFieldKind, CredFieldMeta, CredMap};
 
 pub mod util {
-    use dialoguer::{theme::ColorfulTheme, Input, Password};
+    use dialoguer::{theme::ColorfulTheme, Editor, Input, Password};
     use std::io;
 
     fn theme() -> ColorfulTheme {
@@ -23,6 +23,11 @@ pub mod util {
             .with_prompt(prompt)
             .interact()
     }
+
+    /// Open `$VISUAL` or `$EDITOR`, and returns the edited text (`None` if not saved).
+    pub fn ask_text_with_editor(initial: &str) -> io::Result<Option<String>> {
+        Editor::new().extension(".txt").edit(initial)
+    }
 }
 
 pub fn ask_credential(fields: &[CredFieldMeta]) -> CredMap {
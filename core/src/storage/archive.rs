@@ -0,0 +1,193 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context as _};
+use flate2::read::GzDecoder;
+
+/// Testcase found in an archive or a dir. `output` is `None` for an input-only testcase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedTestcase {
+    pub name: String,
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Input,
+    Output,
+}
+
+/// Extract the zip, tar.gz or tar archive into `dest`.
+/// Entries escaping from `dest` (e.g. "../foo") are not extracted.
+pub fn extract(archive: &Path, dest: &Path) -> anyhow::Result<()> {
+    let filename = archive
+        .file_name()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let file = File::open(archive).with_context(|| format!("Cannot open {:?}", archive))?;
+
+    if filename.ends_with(".zip") {
+        zip::ZipArchive::new(file)
+            .and_then(|mut zip| zip.extract(dest))
+            .with_context(|| format!("Failed to extract zip {:?}", archive))
+    } else if filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
+        tar::Archive::new(GzDecoder::new(file))
+            .unpack(dest)
+            .with_context(|| format!("Failed to extract tar.gz {:?}", archive))
+    } else if filename.ends_with(".tar") {
+        tar::Archive::new(file)
+            .unpack(dest)
+            .with_context(|| format!("Failed to extract tar {:?}", archive))
+    } else {
+        bail!(
+            "Unsupported archive {:?} (Expected .zip, .tar.gz, .tgz or .tar)",
+            archive
+        )
+    }
+}
+
+/// Find pairs of input and output files in the dir, which are laid out as one of:
+///
+/// - `in/{name}[.txt]` and `out/{name}[.txt]` (e.g. the official testcases of AtCoder)
+/// - `{name}.in` and `{name}.out` (or `{name}.ans`)
+/// - `in_{name}.txt` and `out_{name}.txt` (saved by kpr)
+///
+/// Hidden files (e.g. `__MACOSX/`, `.DS_Store`) are ignored.
+pub fn find_testcases(dir: &Path) -> anyhow::Result<Vec<ArchivedTestcase>> {
+    let mut inputs = BTreeMap::new();
+    let mut outputs = BTreeMap::new();
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fsutil::read_dir(&dir)? {
+            let entry = entry.with_context(|| format!("Cannot read dir {:?}", dir))?;
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') || name.starts_with("__") {
+                continue;
+            }
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let Some((role, testcase_name)) = self::classify(&path) else {
+                continue;
+            };
+            let files = match role {
+                Role::Input => &mut inputs,
+                Role::Output => &mut outputs,
+            };
+            if let Some(other) = files.insert(testcase_name.clone(), path.clone()) {
+                bail!(
+                    "Testcase '{}' is found twice: {:?} and {:?}",
+                    testcase_name,
+                    other,
+                    path
+                );
+            }
+        }
+    }
+
+    for (name, path) in &outputs {
+        if !inputs.contains_key(name) {
+            log::warn!("Ignored output without input: {:?}", path);
+        }
+    }
+    Ok(inputs
+        .into_iter()
+        .map(|(name, input)| ArchivedTestcase {
+            output: outputs.remove(&name),
+            name,
+            input,
+        })
+        .collect())
+}
+
+fn classify(path: &Path) -> Option<(Role, String)> {
+    let filename = path.file_name()?.to_str()?;
+    if let Some((stem, ext)) = filename.rsplit_once('.') {
+        match ext {
+            "in" => return Some((Role::Input, stem.to_owned())),
+            "out" | "ans" => return Some((Role::Output, stem.to_owned())),
+            _ => {}
+        }
+    }
+
+    let stem = filename.strip_suffix(".txt").unwrap_or(filename);
+    let parent = path.parent()?.file_name()?.to_str()?;
+    match parent {
+        "in" | "input" => return Some((Role::Input, stem.to_owned())),
+        "out" | "output" | "ans" | "answer" => return Some((Role::Output, stem.to_owned())),
+        _ => {}
+    }
+
+    // e.g. "in1.txt", "in_1.txt", but neither "input.txt" nor "info.txt"
+    let stem = filename.strip_suffix(".txt")?;
+    let strip_prefix = |prefix: &str| {
+        let rest = stem.strip_prefix(prefix)?;
+        if !rest.starts_with(|c: char| c.is_ascii_digit() || c == '_' || c == '-') {
+            return None;
+        }
+        let name = rest.trim_start_matches(['_', '-']);
+        (!name.is_empty()).then(|| name.to_owned())
+    };
+    if let Some(name) = strip_prefix("in") {
+        return Some((Role::Input, name));
+    }
+    if let Some(name) = strip_prefix("out") {
+        return Some((Role::Output, name));
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_find_testcases_in_various_layouts() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path();
+        for file in [
+            "abc300_a/in/01_sample_01.txt",
+            "abc300_a/out/01_sample_01.txt",
+            "abc300_a/in/02_random",
+            "abc300_a/out/02_random",
+            "extra/hand.in",
+            "extra/hand.ans",
+            "extra/in_gen1.txt",
+            "extra/in2.txt",
+            "extra/out2.txt",
+            "__MACOSX/abc300_a/in/._01_sample_01.txt",
+            "README.md",
+            "input.txt",
+            "output.txt",
+            "info.txt",
+        ] {
+            fsutil::write_with_mkdir(dir.join(file), "").unwrap();
+        }
+
+        let testcases = find_testcases(dir).unwrap();
+        let names: Vec<_> = testcases
+            .iter()
+            .map(|t| (t.name.as_str(), t.output.is_some()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("01_sample_01", true),
+                ("02_random", true),
+                ("2", true),
+                ("gen1", false),
+                ("hand", true)
+            ]
+        );
+        assert_eq!(
+            testcases[0].output.as_deref(),
+            Some(dir.join("abc300_a/out/01_sample_01.txt").as_path())
+        );
+    }
+}
@@ -2,6 +2,7 @@ pub mod error {
     pub use ::fsutil::error::*;
 }
 
+pub mod archive;
 pub mod repository;
 pub mod vault;
 pub mod workspace;
@@ -23,6 +23,9 @@ impl ProblemVault {
     const TESTCASE_DIR_NAME: &str = "testcase";
     const PROBLEM_INFO_FILENAME: &str = "problem.json";
 
+    /// Name of the testcase set of the samples, which are saved in `testcase_dir()`.
+    pub const SAMPLE_SET: &str = "sample";
+
     pub fn new(problem_vault_dir: impl Into<PathBuf>) -> Self {
         Self {
             dir: problem_vault_dir.into(),
@@ -53,6 +56,16 @@ impl ProblemVault {
     pub fn testcase_dir(&self) -> PathBuf {
         self.dir.join(Self::TESTCASE_DIR_NAME)
     }
+
+    /// Dir of the named testcase set (e.g. "testcase-full" for "full") imported by the user.
+    pub fn testcase_set_dir(&self, set: &str) -> PathBuf {
+        match set {
+            Self::SAMPLE_SET => self.testcase_dir(),
+            _ => self
+                .dir
+                .join(format!("{}-{}", Self::TESTCASE_DIR_NAME, set)),
+        }
+    }
 }
 
 impl PlatformVault {
@@ -4,8 +4,13 @@ use ::fsutil::{self, OptCopyContents};
 use chrono::{DateTime, Local};
 use kpr_webclient::ProblemInfo;
 
+use anyhow::{bail, ensure};
+
 use super::{error::Result, vault::ProblemVault};
-use crate::testing::{FsTestcase, FsTestcaseFinder};
+use crate::{
+    config::TestcaseLayoutConfig,
+    testing::{FsTestcase, FsTestcaseFinder},
+};
 
 #[derive(Debug, Clone, Copy)]
 pub struct WorkspaceHome<'a> {
@@ -25,7 +30,10 @@ pub struct WorkspaceNameModifier<'categ, 'name> {
 
 impl ProblemWorkspace {
     const TESTCASE_DIR_NAME: &str = "testcase";
+    const USER_TESTCASE_DIR_NAME: &str = "user-testcase";
+    const COMPILE_CACHE_FILE: &str = ".kpr/compile-cache.json";
     const PROBLEM_INFO_FILE: &str = ".problem.json";
+    const TEST_CONFIG_FILE: &str = "kpr-test.toml";
 
     pub fn new(problem_workspace_dir: impl Into<PathBuf>) -> Self {
         Self {
@@ -41,19 +49,88 @@ impl ProblemWorkspace {
         self.dir.join(Self::PROBLEM_INFO_FILE)
     }
 
+    /// Symlink to the testcase dir of the vault, where fetched samples are saved.
     pub fn testcase_dir(&self) -> PathBuf {
         self.dir.join(Self::TESTCASE_DIR_NAME)
     }
 
+    /// Testcases added by the user, which is never touched by fetching.
+    pub fn user_testcase_dir(&self) -> PathBuf {
+        self.dir.join(Self::USER_TESTCASE_DIR_NAME)
+    }
+
+    pub fn test_config_file(&self) -> PathBuf {
+        self.dir.join(Self::TEST_CONFIG_FILE)
+    }
+
+    pub fn compile_cache_file(&self) -> PathBuf {
+        self.dir.join(Self::COMPILE_CACHE_FILE)
+    }
+
+    /// Remove the build dirs (relative to the workspace dir) and the compile cache.
+    /// Returns the removed paths.
+    pub fn remove_build_artifacts(&self, build_dirs: &[impl AsRef<Path>]) -> Result<Vec<PathBuf>> {
+        let mut removed = Vec::new();
+        for dir in build_dirs {
+            let dir = self.dir.join(dir);
+            if dir.is_dir() && !dir.is_symlink() {
+                std::fs::remove_dir_all(&dir)
+                    .map_err(|e| fsutil::Error::SingleIO("Cannot remove dir", dir.to_owned(), e))?;
+                removed.push(dir);
+            }
+        }
+        let cache_file = self.compile_cache_file();
+        if cache_file.is_file() {
+            fsutil::remove_file(&cache_file)?;
+            removed.push(cache_file);
+        }
+        Ok(removed)
+    }
+
+    /// The vault which the problem info file is linked to.
+    pub fn problem_vault(&self) -> Result<ProblemVault> {
+        let info_file = fsutil::canonicalize_path(self.problem_info_file())?;
+        Ok(ProblemVault::new(info_file.parent().unwrap()))
+    }
+
     pub fn load_problem_info(&self) -> Result<ProblemInfo> {
         fsutil::read_json_with_deserialize(self.problem_info_file())
     }
+
+    /// Returns the first name "{prefix}{N}" (N = 1, 2, ...) which is not used by any testcase.
+    pub fn unused_testcase_name(&self, prefix: &str) -> String {
+        let dirs = [self.testcase_dir(), self.user_testcase_dir()];
+        (1..)
+            .map(|n| format!("{}{}", prefix, n))
+            .find(|name| {
+                let (infile, outfile) = ProblemVault::testcase_filename(name);
+                dirs.iter()
+                    .all(|dir| !dir.join(&infile).exists() && !dir.join(&outfile).exists())
+            })
+            .unwrap()
+    }
+
+    /// Save the testcase into the user testcase dir, and returns paths of (input, output) files.
+    pub fn save_user_testcase(
+        &self,
+        name: &str,
+        input: impl AsRef<[u8]>,
+        output: impl AsRef<[u8]>,
+    ) -> Result<(PathBuf, PathBuf)> {
+        let dir = self.user_testcase_dir();
+        let (infile, outfile) = ProblemVault::testcase_filename(name);
+        let (infile, outfile) = (dir.join(infile), dir.join(outfile));
+        fsutil::write_with_mkdir(&infile, input)?;
+        fsutil::write_with_mkdir(&outfile, output)?;
+        Ok((infile, outfile))
+    }
 }
 
 pub struct TestcaseFinder;
 
 impl FsTestcaseFinder for TestcaseFinder {
-    /// if and only if `path` matches "in{...}.txt", find "out{...}.txt" and return them as FsTestcase
+    /// if and only if `path` matches "in{...}.txt", return it with "out{...}.txt" as FsTestcase.
+    /// "out{...}.txt" may not exist (i.e. input-only testcase).
     fn find_by_input_file_path(&self, path: impl AsRef<Path>) -> Option<FsTestcase> {
         let in_file_path = path.as_ref();
         let in_file_name = in_file_path.file_name()?.to_string_lossy();
@@ -65,11 +142,119 @@ impl FsTestcaseFinder for TestcaseFinder {
 
         let out_file_path = in_file_path.with_file_name(format!("out{}", tail));
 
-        if in_file_path.is_file() && out_file_path.is_file() {
-            Some(FsTestcase::new(name, in_file_path, out_file_path))
-        } else {
-            None
+        in_file_path
+            .is_file()
+            .then(|| FsTestcase::new(name, in_file_path, out_file_path))
+    }
+}
+
+/// Finds testcases laid out as `test.testcase_layout[]`, falling back to [`TestcaseFinder`].
+#[derive(Debug, Clone, Default)]
+pub struct PatternTestcaseFinder {
+    layouts: Vec<TestcaseLayout>,
+}
+
+#[derive(Debug, Clone)]
+struct TestcaseLayout {
+    /// Dirs between the testcase dir and the input file (e.g. ["in"] for `in/{name}.txt`)
+    input_dirs: Vec<String>,
+    input_prefix: String,
+    input_suffix: String,
+    output: String,
+}
+
+impl PatternTestcaseFinder {
+    const NAME_VAR: &str = "{name}";
+
+    pub fn new(layouts: &[TestcaseLayoutConfig]) -> anyhow::Result<Self> {
+        let layouts = layouts
+            .iter()
+            .map(Self::parse_layout)
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { layouts })
+    }
+
+    fn parse_layout(cfg: &TestcaseLayoutConfig) -> anyhow::Result<TestcaseLayout> {
+        let mut input_dirs: Vec<_> = cfg.input.split('/').map(str::to_owned).collect();
+        let input_file = input_dirs.pop().unwrap_or_default();
+        let Some((prefix, suffix)) = input_file.split_once(Self::NAME_VAR) else {
+            bail!(
+                "`{}` is missing in the file name of input '{}'",
+                Self::NAME_VAR,
+                cfg.input
+            );
+        };
+        ensure!(
+            !suffix.contains(Self::NAME_VAR)
+                && !input_dirs.iter().any(|d| d.contains(Self::NAME_VAR)),
+            "`{}` must appear once in input '{}'",
+            Self::NAME_VAR,
+            cfg.input
+        );
+        ensure!(
+            cfg.output.contains(Self::NAME_VAR),
+            "`{}` is missing in output '{}'",
+            Self::NAME_VAR,
+            cfg.output
+        );
+        Ok(TestcaseLayout {
+            input_prefix: prefix.to_owned(),
+            input_suffix: suffix.to_owned(),
+            input_dirs,
+            output: cfg.output.to_owned(),
+        })
+    }
+}
+
+impl TestcaseLayout {
+    /// Returns (name, output file path) if the input file path matches the layout.
+    fn match_input(&self, path: &Path) -> Option<(String, PathBuf)> {
+        let name = path
+            .file_name()?
+            .to_str()?
+            .strip_prefix(&self.input_prefix)?
+            .strip_suffix(&self.input_suffix)?;
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut base = path.parent()?;
+        for dir in self.input_dirs.iter().rev() {
+            if base.file_name()? != dir.as_str() {
+                return None;
+            }
+            base = base.parent()?;
         }
+        let output = base.join(self.output.replace(PatternTestcaseFinder::NAME_VAR, name));
+        Some((name.to_owned(), output))
+    }
+}
+
+impl FsTestcaseFinder for PatternTestcaseFinder {
+    /// The first layout whose output file exists is preferred.
+    fn find_by_input_file_path(&self, path: impl AsRef<Path>) -> Option<FsTestcase> {
+        let path = path.as_ref();
+        let candidates: Vec<_> = self
+            .layouts
+            .iter()
+            .filter_map(|layout| layout.match_input(path))
+            .collect();
+        let Some(first) = candidates.first() else {
+            return TestcaseFinder.find_by_input_file_path(path);
+        };
+        let (name, output) = candidates
+            .iter()
+            .find(|(_, output)| output.is_file())
+            .unwrap_or(first);
+        Some(FsTestcase::new(name.to_owned(), path, output.to_owned()))
+    }
+
+    fn max_depth(&self) -> usize {
+        self.layouts
+            .iter()
+            .map(|l| l.input_dirs.len())
+            .max()
+            .unwrap_or(0)
     }
 }
 
@@ -93,6 +278,30 @@ impl<'w> WorkspaceHome<'w> {
         )
     }
 
+    /// Dirs which have the problem info file, found without following symlinks.
+    pub fn find_workspaces(&self) -> Result<Vec<ProblemWorkspace>> {
+        let mut workspaces = Vec::new();
+        if !self.home.is_dir() {
+            return Ok(workspaces);
+        }
+        let mut dirs = vec![self.home.to_owned()];
+        while let Some(dir) = dirs.pop() {
+            let workspace = ProblemWorkspace::new(&dir);
+            if workspace.problem_info_file().exists() {
+                workspaces.push(workspace);
+                continue;
+            }
+            for entry in fsutil::read_dir(&dir)? {
+                let Ok(entry) = entry else { continue };
+                if entry.file_type().is_ok_and(|ft| ft.is_dir()) {
+                    dirs.push(entry.path());
+                }
+            }
+        }
+        workspaces.sort_by(|a, b| a.dir().cmp(b.dir()));
+        Ok(workspaces)
+    }
+
     #[must_use]
     pub fn create_workspace(
         &self,
@@ -123,3 +332,117 @@ impl<'w> WorkspaceHome<'w> {
         Ok(workspace)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{AsyncTestcase, TestcaseOrigin};
+
+    #[test]
+    fn user_testcases_should_be_merged_with_samples() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let workspace = ProblemWorkspace::new(tmpdir.path());
+        fsutil::write_with_mkdir(workspace.testcase_dir().join("in_user1.txt"), "1\n").unwrap();
+        fsutil::write_with_mkdir(workspace.testcase_dir().join("out_user1.txt"), "1\n").unwrap();
+
+        let name = workspace.unused_testcase_name("user");
+        assert_eq!(name, "user2");
+        workspace.save_user_testcase(&name, "2\n", "4\n").unwrap();
+        assert_eq!(workspace.unused_testcase_name("user"), "user3");
+
+        let sources = [
+            (workspace.testcase_dir(), TestcaseOrigin::Sample),
+            (workspace.user_testcase_dir(), TestcaseOrigin::User),
+        ];
+        let testcases = FsTestcase::enumerate_merged(&sources, &TestcaseFinder).unwrap();
+        let names: Vec<_> = testcases
+            .iter()
+            .map(|t| (AsyncTestcase::name(t), t.origin()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("user1", Some(TestcaseOrigin::Sample)),
+                ("user2", Some(TestcaseOrigin::User))
+            ]
+        );
+    }
+
+    #[test]
+    fn build_artifacts_should_be_removed() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let workspace = ProblemWorkspace::new(tmpdir.path().join("2024/0101-Mon/abc/a"));
+        fsutil::write_with_mkdir(workspace.problem_info_file(), "{}").unwrap();
+        fsutil::write_with_mkdir(workspace.dir().join(".kpr/build/main"), "").unwrap();
+        fsutil::write_with_mkdir(workspace.compile_cache_file(), "{}").unwrap();
+        fsutil::write_with_mkdir(workspace.dir().join("main.cpp"), "").unwrap();
+
+        let found = WorkspaceHome::new(tmpdir.path()).find_workspaces().unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].dir(), workspace.dir());
+
+        let removed = found[0].remove_build_artifacts(&[".kpr/build"]).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(!workspace.dir().join(".kpr/build").exists());
+        assert!(!workspace.compile_cache_file().exists());
+        assert!(workspace.dir().join("main.cpp").exists());
+    }
+
+    #[test]
+    fn pattern_finder_should_find_configured_layouts_in_natural_order() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path();
+        for file in [
+            "in/sample10.txt",
+            "out/sample10.txt",
+            "in/sample2.txt",
+            "out/sample2.txt",
+            "hand.in",
+            "hand.ans",
+            "gen.in",
+            "in_user1.txt",
+            "out_user1.txt",
+        ] {
+            fsutil::write_with_mkdir(dir.join(file), "").unwrap();
+        }
+        let layout = |input: &str, output: &str| TestcaseLayoutConfig {
+            input: input.to_owned(),
+            output: output.to_owned(),
+        };
+        let finder = PatternTestcaseFinder::new(&[
+            layout("in/{name}.txt", "out/{name}.txt"),
+            layout("{name}.in", "{name}.out"),
+            layout("{name}.in", "{name}.ans"),
+        ])
+        .unwrap();
+
+        let testcases = FsTestcase::enumerate(dir, &finder).unwrap();
+        let names: Vec<_> = testcases
+            .iter()
+            .map(|t| (AsyncTestcase::name(t), t.has_groundtruth()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("gen", false),
+                ("hand", true),
+                ("sample2", true),
+                ("sample10", true),
+                ("user1", true),
+            ]
+        );
+        assert!(PatternTestcaseFinder::new(&[layout("in/", "out/{name}")]).is_err());
+    }
+
+    #[test]
+    fn input_only_testcase_should_be_found() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        fsutil::write_with_mkdir(tmpdir.path().join("in_gen1.txt"), "1\n").unwrap();
+        fsutil::write_with_mkdir(tmpdir.path().join("out_gen2.txt"), "1\n").unwrap();
+
+        let testcases = FsTestcase::enumerate(tmpdir.path(), &TestcaseFinder).unwrap();
+        assert_eq!(testcases.len(), 1);
+        assert_eq!(AsyncTestcase::name(&testcases[0]), "gen1");
+        assert!(!testcases[0].has_groundtruth());
+    }
+}
@@ -1,9 +1,13 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::Range, time::Duration};
 
 use colored::{Color, ColoredString, Colorize};
 use crossterm::terminal;
 
-use crate::testing::{JudgeCode, TestOutcome};
+use crate::testing::{
+    diff::{self, DiffRow},
+    Comparator, CompileError, JudgeCode, ResourceUsage, RunOutcome, TestOutcome, TestcaseOrigin,
+    TimingStats,
+};
 
 #[macro_export]
 macro_rules! print_success {
@@ -48,7 +52,11 @@ impl ColorTheme for JudgeCode {
                 AC => Color::Green,
                 WA => Color::Yellow,
                 TLE => Color::Red,
+                MLE => Color::BrightRed,
+                OLE => Color::BrightMagenta,
                 RE => Color::Magenta,
+                CE => Color::Blue,
+                UJ => Color::White,
             };
         }
 
@@ -68,11 +76,31 @@ impl ColorTheme for JudgeCode {
                 g: 42,
                 b: 42,
             },
+            MLE => Color::TrueColor {
+                r: 230,
+                g: 90,
+                b: 60,
+            },
+            OLE => Color::TrueColor {
+                r: 200,
+                g: 70,
+                b: 130,
+            },
             RE => Color::TrueColor {
                 r: 171,
                 g: 40,
                 b: 200,
             },
+            CE => Color::TrueColor {
+                r: 60,
+                g: 100,
+                b: 200,
+            },
+            UJ => Color::TrueColor {
+                r: 120,
+                g: 120,
+                b: 120,
+            },
         }
     }
 }
@@ -93,6 +121,62 @@ pub fn judge_icon(judge: JudgeCode) -> ColoredString {
         .color(fg)
 }
 
+/// Name of the testcase, tagged with its origin unless it is a sample.
+pub fn testcase_label(name: &str, origin: Option<TestcaseOrigin>) -> String {
+    match origin {
+        Some(origin) if origin != TestcaseOrigin::Sample => format!("{} [{}]", name, origin),
+        _ => name.to_owned(),
+    }
+}
+
+/// e.g. "123ms, CPU 110ms, 4567KB"
+pub fn resource_usage_summary(res: &TestOutcome) -> String {
+    self::format_resource_usage(res.execution_time, res.resource_usage)
+}
+
+fn format_resource_usage(execution_time: Duration, usage: Option<ResourceUsage>) -> String {
+    match usage {
+        Some(usage) => format!(
+            "{}ms, CPU {}ms, {}KB",
+            execution_time.as_millis(),
+            usage.cpu_time.as_millis(),
+            usage.peak_memory_kb,
+        ),
+        None => format!("{}ms", execution_time.as_millis()),
+    }
+}
+
+/// Printed to stderr so as not to be mixed with the stdout of the program.
+pub fn print_run_outcome(res: &RunOutcome) {
+    let status = match &res.reason {
+        Some(reason) => reason.bright_red().bold(),
+        None => "exit code 0".green().bold(),
+    };
+    eprintln!(
+        "{} [{}]",
+        status,
+        self::format_resource_usage(res.execution_time, res.resource_usage)
+    );
+}
+
+/// e.g. "min 10ms / median 12ms / max 15ms"
+pub fn timing_stats_summary(stats: &TimingStats) -> String {
+    format!(
+        "min {}ms / median {}ms / max {}ms",
+        stats.min.as_millis(),
+        stats.median.as_millis(),
+        stats.max.as_millis(),
+    )
+}
+
+pub fn clear_screen() {
+    let _ = crossterm::execute!(
+        std::io::stdout(),
+        terminal::Clear(terminal::ClearType::All),
+        crossterm::cursor::MoveTo(0, 0),
+    );
+}
+
 pub fn contest_problem_serial_code_generator(num_problems_in_contest: usize) -> fn(u32) -> String {
     let serial_code = if num_problems_in_contest <= 26 {
         // 1 => "a",  2 => "b",  3 => "c", ...
@@ -113,14 +197,23 @@ pub fn print_test_result_summary(results: &[TestOutcome]) {
     });
 
     let num_total_test = results.len();
-    let num_passed = *count.get(&JudgeCode::AC).unwrap_or(&0);
-    let num_failed = num_total_test - num_passed;
+    let num_unjudged = *count.get(&JudgeCode::UJ).unwrap_or(&0);
+    let num_failed = results.iter().filter(|r| r.judge.is_failure()).count();
 
-    if num_passed == num_total_test {
-        let msg = format!("All {} tests passed ✨", num_total_test);
+    if num_failed == 0 {
+        let msg = if num_unjudged == 0 {
+            format!("All {} tests passed ✨", num_total_test)
+        } else {
+            format!(
+                "{}/{} tests passed ✨ ({} unjudged)",
+                num_total_test - num_unjudged,
+                num_total_test,
+                num_unjudged
+            )
+        };
         print!("{}", msg.green());
     } else {
-        let summary_msg = if num_passed > 0 {
+        let summary_msg = if num_failed < num_total_test {
             format!("{}/{} tests failed 💣", num_failed, num_total_test)
         } else {
             format!("All {} tests failed 💀", num_total_test)
@@ -144,6 +237,30 @@ pub fn print_test_result_summary(results: &[TestOutcome]) {
     }
 
     println!(" {}", bar);
+
+    // Group the failures by the reason, in the order of testcases.
+    let mut reasons: Vec<(JudgeCode, &str, Vec<String>)> = Vec::new();
+    for r in results.iter().filter(|r| r.judge.is_failure()) {
+        let Some(reason) = r.reason.as_deref() else {
+            continue;
+        };
+        let label = self::testcase_label(&r.testcase_name, r.origin);
+        match reasons
+            .iter_mut()
+            .find(|(judge, why, _)| *judge == r.judge && *why == reason)
+        {
+            Some((_, _, labels)) => labels.push(label),
+            None => reasons.push((r.judge, reason, vec![label])),
+        }
+    }
+    for (judge, reason, labels) in reasons {
+        println!(
+            "  {} {} {}",
+            self::judge_icon(judge),
+            reason,
+            format!("({})", labels.join(", ")).dimmed()
+        );
+    }
 }
 
 pub fn print_test_result_detail(res: &TestOutcome) {
@@ -158,11 +275,18 @@ pub fn print_test_result_detail(res: &TestOutcome) {
     let bold_bar = BOLD_LINE.repeat(cols as usize).blue().bold();
 
     let title_color = Color::BrightYellow;
+    let reason = match &res.reason {
+        Some(reason) => format!(" {}", reason.bright_red()),
+        None => String::new(),
+    };
     println!(
-        "\n{}: {} [{}ms]\n{}",
-        res.testcase_name.color(title_color).bold(),
+        "\n{}: {}{} [{}]\n{}",
+        self::testcase_label(&res.testcase_name, res.origin)
+            .color(title_color)
+            .bold(),
         self::judge_icon(res.judge),
-        res.execution_time.as_millis(),
+        reason,
+        self::resource_usage_summary(res),
         bold_bar,
     );
 
@@ -174,8 +298,8 @@ pub fn print_test_result_detail(res: &TestOutcome) {
         )
     }
 
-    fn print_lines(lines: &[&str], entire_str: &str) {
-        if lines.is_empty() {
+    fn print_lines(lines: &[&str], entire_str: &str, truncated_bytes: usize) {
+        if lines.is_empty() && truncated_bytes == 0 {
             println!("{}", "<EMPTY>".magenta().dimmed());
             return;
         }
@@ -193,23 +317,361 @@ pub fn print_test_result_detail(res: &TestOutcome) {
             }
 
             let is_last_line = i + 1 == lines.len();
-            if is_last_line && !entire_str.ends_with("\n") {
+            if is_last_line && truncated_bytes == 0 && !entire_str.ends_with("\n") {
                 print!("{}", " Missing new line ".on_yellow().black().bold());
             }
 
             println!("");
         }
+        if truncated_bytes > 0 {
+            println!(
+                "{}",
+                format!(" ... {} more bytes truncated ", truncated_bytes)
+                    .on_blue()
+                    .white()
+                    .bold()
+            );
+        }
     }
 
-    print_sub_title("[truth-answer]", cols as usize);
-    print_lines(&truth_lines, &res.groundtruth);
+    if let Some(transcript) = &res.transcript {
+        print_sub_title("[transcript]", cols as usize);
+        print_lines(&transcript.lines().collect::<Vec<_>>(), transcript, 0);
+    } else if res.judge == JudgeCode::UJ {
+        print_sub_title("[stdout]", cols as usize);
+        print_lines(
+            &stdout_lines,
+            &res.output.stdout,
+            res.output.stdout_truncated_bytes,
+        );
+    } else if let (JudgeCode::WA, Some(comparator)) = (res.judge, res.comparator) {
+        print_sub_title("[diff]", cols as usize);
+        self::print_diff(res, comparator, cols as usize);
+    } else {
+        print_sub_title("[truth-answer]", cols as usize);
+        print_lines(&truth_lines, &res.groundtruth, 0);
+
+        print_sub_title("[stdout]", cols as usize);
+        print_lines(
+            &stdout_lines,
+            &res.output.stdout,
+            res.output.stdout_truncated_bytes,
+        );
+    }
 
-    print_sub_title("[stdout]", cols as usize);
-    print_lines(&stdout_lines, &res.output.stdout);
+    if let Some(msg) = &res.checker_message {
+        let title = if res.transcript.is_some() {
+            "[interactor]"
+        } else {
+            "[checker]"
+        };
+        print_sub_title(title, cols as usize);
+        println!("{}", msg);
+    }
 
     if !res.output.stderr.is_empty() {
         print_sub_title("[stderr]", cols as usize);
         print!("{}", res.output.stderr);
+        if res.output.stderr_truncated_bytes > 0 {
+            if !res.output.stderr.ends_with('\n') {
+                println!();
+            }
+            println!(
+                "{}",
+                format!(
+                    " ... {} more bytes truncated ",
+                    res.output.stderr_truncated_bytes
+                )
+                .on_blue()
+                .white()
+                .bold()
+            );
+        }
+    }
+
+    println!("{}", bold_bar);
+}
+
+/// The diff is shown side by side if the terminal is at least this wide, or unified otherwise.
+const DIFF_SIDE_BY_SIDE_MIN_COLS: usize = 100;
+
+/// Number of identical lines shown around the differences
+const DIFF_CONTEXT_LINES: usize = 3;
+
+fn print_diff(res: &TestOutcome, comparator: Comparator, cols: usize) {
+    let mut stdout_lines: Vec<_> = res.output.stdout.lines().collect();
+    let mut truth_lines: Vec<_> = res.groundtruth.lines().collect();
+    let truncated = res.output.stdout_truncated_bytes > 0;
+    if truncated {
+        // The last line may be cut in the middle, and the rest of the output is unknown.
+        if !res.output.stdout.ends_with('\n') {
+            stdout_lines.pop();
+        }
+        truth_lines.truncate(stdout_lines.len());
+    }
+
+    let rows = diff::diff_lines(&truth_lines, &stdout_lines, comparator, DIFF_CONTEXT_LINES);
+    // Only windows around the first mismatch are kept for huge outputs.
+    let omitted = res.omitted_lines;
+    let number_width = (truth_lines.len() + omitted.groundtruth)
+        .max(stdout_lines.len() + omitted.stdout)
+        .max(1)
+        .to_string()
+        .len();
+    let number =
+        |line: diff::Line, offset: usize| format!("{:>w$}", line.number + offset, w = number_width);
+    let truth_number = |line| number(line, omitted.groundtruth);
+    let output_number = |line| number(line, omitted.stdout);
+    let no_number = " ".repeat(number_width);
+    let print_omitted = || {
+        if omitted.stdout > 0 {
+            let msg = format!("... {} identical lines ...", omitted.stdout);
+            println!("{}", msg.dimmed());
+        }
+    };
+
+    if cols >= DIFF_SIDE_BY_SIDE_MIN_COLS {
+        // "{number} │ {text}" on both sides, separated by " ┃ "
+        let text_width = (cols - 3) / 2 - number_width - 3;
+        let side = |number: &str, cell: String| {
+            format!("{} {} {}", number.dimmed(), "│".bright_black(), cell)
+        };
+        println!(
+            "{}{}{}",
+            format!("{:w$}", "truth-answer", w = text_width + number_width + 3).cyan(),
+            " ┃ ".bright_black(),
+            "stdout".cyan(),
+        );
+        print_omitted();
+        for row in &rows {
+            let (left, right) = match *row {
+                DiffRow::Same { truth, output } => (
+                    side(
+                        &truth_number(truth),
+                        self::diff_cell(truth.text, None, None, text_width),
+                    ),
+                    side(
+                        &output_number(output),
+                        self::diff_cell(output.text, None, None, text_width),
+                    ),
+                ),
+                DiffRow::Changed {
+                    truth,
+                    output,
+                    first_mismatch,
+                } => (
+                    side(
+                        &truth_number(truth),
+                        self::diff_cell(
+                            truth.text,
+                            Some(Color::Red),
+                            Some(self::mismatch_span(truth.text, first_mismatch)),
+                            text_width,
+                        ),
+                    ),
+                    side(
+                        &output_number(output),
+                        self::diff_cell(
+                            output.text,
+                            Some(Color::Green),
+                            Some(self::mismatch_span(output.text, first_mismatch)),
+                            text_width,
+                        ),
+                    ),
+                ),
+                DiffRow::TruthOnly(truth) => (
+                    side(
+                        &truth_number(truth),
+                        self::diff_cell(truth.text, Some(Color::Red), None, text_width),
+                    ),
+                    String::new(),
+                ),
+                DiffRow::OutputOnly(output) => (
+                    side(&no_number, " ".repeat(text_width)),
+                    side(
+                        &output_number(output),
+                        self::diff_cell(output.text, Some(Color::Green), None, text_width),
+                    ),
+                ),
+                DiffRow::Collapsed(n) => {
+                    println!("{}", format!("... {} identical lines ...", n).dimmed());
+                    continue;
+                }
+            };
+            println!("{}{}{}", left, " ┃ ".bright_black(), right.trim_end());
+        }
+    } else {
+        println!("{} {}", "- truth-answer".red(), "+ stdout".green());
+        print_omitted();
+        let text_width = cols.saturating_sub(number_width + 2).max(1);
+        let print_line = |sign: &str, number: String, line: diff::Line, color, highlight| {
+            let cell = self::diff_cell(line.text, color, highlight, text_width);
+            let prefix = format!("{}{}", sign, number);
+            let prefix = match color {
+                Some(color) => prefix.color(color),
+                None => prefix.dimmed(),
+            };
+            println!("{} {}", prefix, cell.trim_end());
+        };
+        for row in &rows {
+            match *row {
+                DiffRow::Same { output, .. } => {
+                    print_line(" ", output_number(output), output, None, None)
+                }
+                DiffRow::Changed {
+                    truth,
+                    output,
+                    first_mismatch,
+                } => {
+                    let truth_span = self::mismatch_span(truth.text, first_mismatch);
+                    let output_span = self::mismatch_span(output.text, first_mismatch);
+                    print_line(
+                        "-",
+                        truth_number(truth),
+                        truth,
+                        Some(Color::Red),
+                        Some(truth_span),
+                    );
+                    print_line(
+                        "+",
+                        output_number(output),
+                        output,
+                        Some(Color::Green),
+                        Some(output_span),
+                    );
+                }
+                DiffRow::TruthOnly(truth) => {
+                    print_line("-", truth_number(truth), truth, Some(Color::Red), None)
+                }
+                DiffRow::OutputOnly(output) => {
+                    print_line("+", output_number(output), output, Some(Color::Green), None)
+                }
+                DiffRow::Collapsed(n) => {
+                    println!("{}", format!("... {} identical lines ...", n).dimmed())
+                }
+            }
+        }
+    }
+
+    let has_difference = rows
+        .iter()
+        .any(|row| !matches!(row, DiffRow::Same { .. } | DiffRow::Collapsed(_)));
+    if truncated {
+        println!(
+            "{}",
+            format!(
+                " ... {} more bytes of stdout truncated ",
+                res.output.stdout_truncated_bytes
+            )
+            .on_blue()
+            .white()
+            .bold()
+        );
+    } else if !has_difference {
+        if res.groundtruth.ends_with('\n') && !res.output.stdout.ends_with('\n') {
+            println!("{}", " Missing new line ".on_yellow().black().bold());
+        } else {
+            println!("{}", "No difference in lines".bright_red().bold());
+        }
+    }
+}
+
+/// Line of the diff, cut and padded to `width` chars.
+/// The `highlight` byte range is emphasized if `color` is given.
+fn diff_cell(
+    text: &str,
+    color: Option<Color>,
+    highlight: Option<Range<usize>>,
+    width: usize,
+) -> String {
+    let num_chars = text.chars().count();
+    let (text, ellipsis) = if num_chars > width {
+        let end = text
+            .char_indices()
+            .nth(width.saturating_sub(1))
+            .map_or(text.len(), |(i, _)| i);
+        (&text[..end], "…")
+    } else {
+        (text, "")
+    };
+    let padding = " ".repeat(width.saturating_sub(num_chars));
+
+    let Some(color) = color else {
+        return format!("{}{}{}", text, ellipsis, padding);
+    };
+    let span = highlight.map_or(text.len()..text.len(), |r| {
+        r.start.min(text.len())..r.end.min(text.len())
+    });
+    let paint = |s: &str, emphasized: bool| match s {
+        "" => String::new(),
+        s if !emphasized => s.color(color).to_string(),
+        s if s.trim().is_empty() => s.on_red().to_string(),
+        s => s.color(color).bold().reversed().to_string(),
+    };
+    format!(
+        "{}{}{}{}{}",
+        paint(&text[..span.start], false),
+        paint(&text[span.clone()], true),
+        paint(&text[span.end..], false),
+        paint(ellipsis, false),
+        padding,
+    )
+}
+
+/// The first mismatched token, or trailing whitespaces if only whitespaces differ.
+/// The end of the line if the line has fewer tokens.
+fn mismatch_span(text: &str, first_mismatch: Option<usize>) -> Range<usize> {
+    match first_mismatch {
+        Some(index) => diff::token_span(text, index).unwrap_or(text.len()..text.len()),
+        None => text.trim_end().len()..text.len(),
+    }
+}
+
+/// Max number of lines of compiler diagnostics to be printed
+const COMPILE_ERROR_MAX_LINES: usize = 30;
+
+/// Number of lines printed before the first error (e.g. "In function 'int main()':")
+const COMPILE_ERROR_LEADING_LINES: usize = 3;
+
+pub fn print_compile_error(role: &str, err: &CompileError) {
+    let (cols, _) = terminal::size().unwrap_or((40, 40));
+    let bold_bar = "━".repeat(cols as usize).blue().bold();
+
+    println!(
+        "\n{}: {}\n{}\n{}",
+        format!("Compile {}", role).bright_yellow().bold(),
+        self::judge_icon(JudgeCode::CE),
+        err.command.dimmed(),
+        bold_bar,
+    );
+
+    let diagnostics = err.output.stderr.clone() + &err.output.stdout;
+    let lines: Vec<_> = diagnostics.lines().collect();
+    let first_error = lines
+        .iter()
+        .position(|line| line.to_lowercase().contains("error"));
+
+    let start = first_error.map_or(0, |i| i.saturating_sub(COMPILE_ERROR_LEADING_LINES));
+    let end = lines.len().min(start + COMPILE_ERROR_MAX_LINES);
+
+    if start > 0 {
+        println!("{}", format!("... ({} lines omitted)", start).dimmed());
+    }
+    for (i, line) in lines.iter().enumerate().take(end).skip(start) {
+        if Some(i) == first_error {
+            println!("{}", line.bright_red().bold());
+        } else {
+            println!("{}", line);
+        }
+    }
+    if end < lines.len() {
+        println!(
+            "{}",
+            format!("... ({} more lines)", lines.len() - end).dimmed()
+        );
+    }
+    if lines.is_empty() {
+        println!("{}", "<EMPTY>".magenta().dimmed());
     }
 
     println!("{}", bold_bar);
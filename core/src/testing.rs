@@ -1,7 +1,24 @@
+pub mod checker;
+pub mod comparator;
+pub mod compile_cache;
+pub mod diff;
+pub mod interactor;
+pub mod process;
+pub mod report;
 pub mod result;
 pub mod runner;
+pub mod sandbox;
+pub mod shrinker;
+pub mod streaming;
 pub mod testcase;
 
+pub use checker::*;
+pub use comparator::*;
+pub use compile_cache::CompileCache;
+pub use interactor::Interactor;
+pub use report::ReportFormat;
 pub use result::*;
 pub use runner::*;
+pub use sandbox::Sandbox;
+pub use shrinker::{InputValidator, ProgramValidator, ShrinkResult, Shrinker};
 pub use testcase::*;
@@ -0,0 +1,114 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use super::{
+    result::ProcessOutput,
+    runner::{TestCommand, TestRunner},
+};
+use crate::str_interp::InterpError;
+
+/// External special judge program (testlib-style).
+///
+/// The checker is invoked as `<run> <input_file> <output_file> <answer_file>`
+/// and the output is accepted if and only if it exits with code 0.
+/// The checker is killed with its descendants if it exceeds the time limit.
+#[derive(Debug, Clone)]
+pub struct Checker {
+    runner: TestRunner,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckerVerdict {
+    pub accepted: bool,
+    pub message: String,
+}
+
+impl Checker {
+    /// Exit codes of testlib: 0=OK, 1=WA, 2=PE. Others mean failure of the checker itself.
+    const TESTLIB_WA_EXIT_CODES: [i32; 2] = [1, 2];
+
+    const DEFAULT_TIME_LIMIT: Duration = Duration::from_secs(10);
+
+    pub fn new(cmd: TestCommand) -> Self {
+        Self {
+            runner: TestRunner::new(cmd).execution_time_limit(Self::DEFAULT_TIME_LIMIT),
+        }
+    }
+
+    pub fn time_limit(mut self, limit: Duration) -> Self {
+        self.runner = self.runner.execution_time_limit(limit);
+        self
+    }
+
+    pub fn shell(mut self, shell: impl Into<PathBuf>) -> Self {
+        self.runner = self.runner.shell(shell);
+        self
+    }
+
+    pub fn program_file(
+        mut self,
+        filepath: impl AsRef<Path>,
+    ) -> std::result::Result<Self, InterpError> {
+        self.runner = self.runner.program_file(filepath)?;
+        Ok(self)
+    }
+
+    pub fn get_runner(&self) -> &TestRunner {
+        &self.runner
+    }
+
+    pub fn get_command(&self) -> &TestCommand {
+        self.runner.get_command()
+    }
+
+    pub fn is_compile_cmd_defined(&self) -> bool {
+        self.runner.is_compile_cmd_defined()
+    }
+
+    pub async fn compile(&self) -> anyhow::Result<ProcessOutput> {
+        self.runner.compile().await
+    }
+
+    pub async fn check(
+        &self,
+        input_file: &Path,
+        output_file: &Path,
+        answer_file: &Path,
+    ) -> anyhow::Result<CheckerVerdict> {
+        let output = self
+            .runner
+            .exec_within_time_limit(&[input_file, output_file, answer_file], b"")
+            .await?;
+        let Some(output) = output else {
+            return Ok(CheckerVerdict {
+                accepted: false,
+                message: "Checker failed (timeout)".to_owned(),
+            });
+        };
+
+        let mut message = String::from_utf8_lossy(&output.stdout).into_owned();
+        message += &String::from_utf8_lossy(&output.stderr);
+        let message = message.trim_end().to_owned();
+
+        match output.status.code() {
+            Some(0) => Ok(CheckerVerdict {
+                accepted: true,
+                message,
+            }),
+            Some(code) if Self::TESTLIB_WA_EXIT_CODES.contains(&code) => Ok(CheckerVerdict {
+                accepted: false,
+                message,
+            }),
+            code => Ok(CheckerVerdict {
+                accepted: false,
+                message: format!(
+                    "Checker failed (exitcode={})\n{}",
+                    code.map_or("None".to_owned(), |c| c.to_string()),
+                    message
+                ),
+            }),
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::{fmt, str::FromStr};
+
+use serde::Deserialize;
+
+/// Decides whether the output of a program is equivalent to the groundtruth.
+///
+/// String representation (used in `kpr.toml` and `kpr test --judge`):
+/// - `exact`
+/// - `token`
+/// - `float`, `float:<eps>`, `float:<abs_eps>:<rel_eps>`
+/// - `ignore-case`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Comparator {
+    /// Byte-by-byte comparison.
+    #[default]
+    Exact,
+
+    /// Compares whitespace-separated tokens, so that the amount of spaces and newlines is ignored.
+    Token,
+
+    /// Compares tokens, regarding two tokens as equal if both are numbers within the error.
+    Float { abs_eps: f64, rel_eps: f64 },
+
+    /// Compares tokens ignoring ASCII case (e.g. "Yes" == "YES").
+    IgnoreCase,
+}
+
+// Epsilons are never NaN because `Comparator::from_str` rejects them.
+impl Eq for Comparator {}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid comparator '{0}' (expected one of: exact, token, float[:<eps>[:<rel_eps>]], ignore-case)")]
+pub struct ParseComparatorError(String);
+
+impl Comparator {
+    pub const DEFAULT_FLOAT_EPS: f64 = 1e-6;
+
+    pub fn matches(&self, groundtruth: &str, output: &str) -> bool {
+        match self {
+            Comparator::Exact => groundtruth == output,
+            _ => Self::zip_tokens(groundtruth, output, |a, b| self.token_matches(a, b)),
+        }
+    }
+
+    /// Whether two whitespace-separated tokens are regarded as equal.
+    /// For `Exact`, tokens are compared byte by byte.
+    pub fn token_matches(&self, groundtruth: &str, output: &str) -> bool {
+        self.bytes_token_matches(groundtruth.as_bytes(), output.as_bytes())
+    }
+
+    /// Same as [`Comparator::token_matches`], but for tokens which may not be valid UTF-8.
+    pub fn bytes_token_matches(&self, groundtruth: &[u8], output: &[u8]) -> bool {
+        use Comparator::*;
+        match *self {
+            Exact | Token => groundtruth == output,
+            Float { abs_eps, rel_eps } => {
+                groundtruth == output || Self::float_token_eq(groundtruth, output, abs_eps, rel_eps)
+            }
+            IgnoreCase => groundtruth.eq_ignore_ascii_case(output),
+        }
+    }
+
+    fn zip_tokens(a: &str, b: &str, eq: impl Fn(&str, &str) -> bool) -> bool {
+        let mut a = a.split_ascii_whitespace();
+        let mut b = b.split_ascii_whitespace();
+        loop {
+            match (a.next(), b.next()) {
+                (None, None) => return true,
+                (Some(x), Some(y)) if eq(x, y) => continue,
+                _ => return false,
+            }
+        }
+    }
+
+    fn float_token_eq(groundtruth: &[u8], output: &[u8], abs_eps: f64, rel_eps: f64) -> bool {
+        let parse = |token: &[u8]| std::str::from_utf8(token).ok()?.parse::<f64>().ok();
+        let (Some(expected), Some(actual)) = (parse(groundtruth), parse(output)) else {
+            return false;
+        };
+        if !actual.is_finite() {
+            return false;
+        }
+        let err = (expected - actual).abs();
+        err <= abs_eps || err <= rel_eps * expected.abs()
+    }
+}
+
+impl FromStr for Comparator {
+    type Err = ParseComparatorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseComparatorError(s.to_owned());
+        let parse_eps = |x: &str| match x.parse::<f64>() {
+            Ok(eps) if eps.is_finite() && eps >= 0.0 => Ok(eps),
+            _ => Err(err()),
+        };
+
+        let mut parts = s.split(':');
+        let kind = parts.next().unwrap_or_default();
+        let args: Vec<&str> = parts.collect();
+
+        use Comparator::*;
+        match (kind, args.as_slice()) {
+            ("exact", []) => Ok(Exact),
+            ("token", []) => Ok(Token),
+            ("ignore-case", []) => Ok(IgnoreCase),
+            ("float", []) => Ok(Float {
+                abs_eps: Self::DEFAULT_FLOAT_EPS,
+                rel_eps: Self::DEFAULT_FLOAT_EPS,
+            }),
+            ("float", [eps]) => {
+                let eps = parse_eps(eps)?;
+                Ok(Float {
+                    abs_eps: eps,
+                    rel_eps: eps,
+                })
+            }
+            ("float", [abs_eps, rel_eps]) => Ok(Float {
+                abs_eps: parse_eps(abs_eps)?,
+                rel_eps: parse_eps(rel_eps)?,
+            }),
+            _ => Err(err()),
+        }
+    }
+}
+
+impl TryFrom<String> for Comparator {
+    type Error = ParseComparatorError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Comparator::*;
+        match self {
+            Exact => write!(f, "exact"),
+            Token => write!(f, "token"),
+            Float { abs_eps, rel_eps } if abs_eps == rel_eps => write!(f, "float:{}", abs_eps),
+            Float { abs_eps, rel_eps } => write!(f, "float:{}:{}", abs_eps, rel_eps),
+            IgnoreCase => write!(f, "ignore-case"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_comparator_ok() {
+        use Comparator::*;
+        assert_eq!("exact".parse(), Ok(Exact));
+        assert_eq!("token".parse(), Ok(Token));
+        assert_eq!("ignore-case".parse(), Ok(IgnoreCase));
+        assert_eq!(
+            "float".parse(),
+            Ok(Float {
+                abs_eps: 1e-6,
+                rel_eps: 1e-6
+            })
+        );
+        assert_eq!(
+            "float:1e-9".parse(),
+            Ok(Float {
+                abs_eps: 1e-9,
+                rel_eps: 1e-9
+            })
+        );
+        assert_eq!(
+            "float:0:1e-3".parse(),
+            Ok(Float {
+                abs_eps: 0.0,
+                rel_eps: 1e-3
+            })
+        );
+    }
+
+    #[test]
+    fn parse_comparator_ng() {
+        for s in [
+            "",
+            "Exact",
+            "exact:1",
+            "float:",
+            "float:abc",
+            "float:-1",
+            "float:NaN",
+            "float:1:2:3",
+        ] {
+            assert!(s.parse::<Comparator>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn display_should_be_parsable() {
+        for s in [
+            "exact",
+            "token",
+            "ignore-case",
+            "float:0.000001",
+            "float:0.5:0.25",
+        ] {
+            let c: Comparator = s.parse().unwrap();
+            assert_eq!(c.to_string().parse::<Comparator>().unwrap(), c);
+        }
+    }
+
+    #[test]
+    fn exact_comparator() {
+        let c = Comparator::Exact;
+        assert!(c.matches("1 2\n", "1 2\n"));
+        assert!(!c.matches("1 2\n", "1  2\n"));
+        assert!(!c.matches("1 2\n", "1 2"));
+    }
+
+    #[test]
+    fn token_comparator() {
+        let c = Comparator::Token;
+        assert!(c.matches("1 2\n", "1  2"));
+        assert!(c.matches("1\n2\n", " 1 2 \n\n"));
+        assert!(!c.matches("1 2\n", "1 2 3\n"));
+        assert!(!c.matches("Yes\n", "YES\n"));
+    }
+
+    #[test]
+    fn float_comparator() {
+        let c: Comparator = "float:1e-6".parse().unwrap();
+        assert!(c.matches("0.5000000\n", "0.5\n"));
+        assert!(c.matches("1.0\n", "1.0000009\n"));
+        assert!(!c.matches("1.0\n", "1.000002\n"));
+        // relative error
+        assert!(c.matches("1000000000\n", "1000000100\n"));
+        assert!(!c.matches("1000000000\n", "1000002000\n"));
+        // non-numeric tokens are compared exactly
+        assert!(c.matches("Yes 0.1\n", "Yes 0.1000001\n"));
+        assert!(!c.matches("Yes 0.1\n", "No 0.1\n"));
+        assert!(!c.matches("1.0\n", "nan\n"));
+    }
+
+    #[test]
+    fn ignore_case_comparator() {
+        let c = Comparator::IgnoreCase;
+        assert!(c.matches("Yes\n", "YES\n"));
+        assert!(c.matches("Yes\n", "yes"));
+        assert!(!c.matches("Yes\n", "No\n"));
+    }
+}
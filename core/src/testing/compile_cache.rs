@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    io,
+    os::unix::ffi::OsStrExt as _,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+use super::runner::TestCommand;
+
+/// Fingerprints of the last successful compilations, keyed on the command which runs the product
+/// (e.g. `./main`).
+///
+/// Compilation can be skipped while the fingerprint is unchanged and the product is still the one
+/// made by the compilation. Since the product is identified by its modification time, the entry
+/// is invalidated when another profile overwrites the same executable.
+#[derive(Debug, Clone)]
+pub struct CompileCache {
+    file: PathBuf,
+}
+
+impl CompileCache {
+    pub fn new(file: impl Into<PathBuf>) -> Self {
+        Self { file: file.into() }
+    }
+
+    /// `product` is the file made by the compilation, which must be unchanged since then.
+    pub fn is_up_to_date(&self, key: &str, fingerprint: &str, product: &Path) -> bool {
+        let Some(product_modified) = Self::modified(product) else {
+            return false;
+        };
+        self.load().get(key).is_some_and(|entry| {
+            entry.fingerprint == fingerprint && entry.product_modified == Some(product_modified)
+        })
+    }
+
+    pub fn update(&self, key: &str, fingerprint: String, product: &Path) -> fsutil::Result<()> {
+        let mut entries = self.load();
+        let entry = Entry {
+            fingerprint,
+            product_modified: Self::modified(product),
+        };
+        entries.insert(key.to_owned(), entry);
+        fsutil::write_json_with_mkdir(&self.file, &entries)
+    }
+
+    fn load(&self) -> HashMap<String, Entry> {
+        fsutil::read_json_with_deserialize(&self.file).unwrap_or_default()
+    }
+
+    fn modified(file: &Path) -> Option<SystemTime> {
+        std::fs::metadata(file).and_then(|m| m.modified()).ok()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    fingerprint: String,
+    product_modified: Option<SystemTime>,
+}
+
+/// SHA-256 of the shell, the compile command with its `cwd` and `env`, and the contents of the
+/// source files. A stable hash is used, since it is stored in the cache file.
+pub fn fingerprint(
+    shell: &Path,
+    cmd: &TestCommand,
+    source_files: &[impl AsRef<Path>],
+) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    // Each field is prefixed with its length, so that the boundaries are unambiguous.
+    let mut update = |bytes: &[u8]| {
+        hasher.update((bytes.len() as u64).to_le_bytes());
+        hasher.update(bytes);
+    };
+    update(shell.as_os_str().as_bytes());
+    update(cmd.compile.as_deref().unwrap_or_default().as_bytes());
+    update(cmd.cwd.as_deref().unwrap_or_default().as_bytes());
+    update(&(cmd.env.len() as u64).to_le_bytes());
+    for (key, value) in &cmd.env {
+        update(key.as_bytes());
+        update(value.as_bytes());
+    }
+    for file in source_files {
+        let file = file.as_ref();
+        update(file.as_os_str().as_bytes());
+        update(&std::fs::read(file)?);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn compile_cmd(compile: &str) -> TestCommand {
+        TestCommand {
+            compile: Some(compile.to_owned()),
+            run: "./main".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fingerprint_should_change_with_command_or_source() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let src = tmpdir.path().join("main.cpp");
+        fsutil::write(&src, "int main() {}").unwrap();
+        let product = tmpdir.path().join("main");
+        fsutil::write(&product, "").unwrap();
+        let sh = Path::new("/bin/sh");
+
+        let cache = CompileCache::new(tmpdir.path().join(".kpr/compile-cache.json"));
+        let cmd = compile_cmd("g++ main.cpp -o main");
+        let fp = fingerprint(sh, &cmd, &[&src]).unwrap();
+        assert_eq!(fp.len(), 64);
+        assert!(!cache.is_up_to_date("./main", &fp, &product));
+
+        cache.update("./main", fp.clone(), &product).unwrap();
+        assert!(cache.is_up_to_date("./main", &fp, &product));
+        assert!(!cache.is_up_to_date("./main-debug", &fp, &product));
+
+        let debug_cmd = compile_cmd("g++ -fsanitize=address main.cpp -o main");
+        let debug_fp = fingerprint(sh, &debug_cmd, &[&src]).unwrap();
+        assert!(!cache.is_up_to_date("./main", &debug_fp, &product));
+
+        let bash_fp = fingerprint(Path::new("/bin/bash"), &cmd, &[&src]).unwrap();
+        assert!(!cache.is_up_to_date("./main", &bash_fp, &product));
+        let mut env_cmd = cmd.clone();
+        env_cmd
+            .env
+            .insert("CPATH".to_owned(), "/opt/include".to_owned());
+        let env_fp = fingerprint(sh, &env_cmd, &[&src]).unwrap();
+        assert!(!cache.is_up_to_date("./main", &env_fp, &product));
+
+        fsutil::write(&src, "int main() { return 0; }").unwrap();
+        let fp = fingerprint(sh, &cmd, &[&src]).unwrap();
+        assert!(!cache.is_up_to_date("./main", &fp, &product));
+    }
+
+    #[test]
+    fn cache_should_be_invalidated_by_change_of_product() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let product = tmpdir.path().join("main");
+        fsutil::write(&product, "").unwrap();
+
+        let cache = CompileCache::new(tmpdir.path().join(".kpr/compile-cache.json"));
+        cache.update("./main", "fp".to_owned(), &product).unwrap();
+        assert!(cache.is_up_to_date("./main", "fp", &product));
+
+        // Overwritten (e.g. by another profile)
+        let file = std::fs::File::options().write(true).open(&product).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(1))
+            .unwrap();
+        assert!(!cache.is_up_to_date("./main", "fp", &product));
+
+        // Removed (e.g. by `kpr clean`)
+        cache.update("./main", "fp".to_owned(), &product).unwrap();
+        fsutil::remove_file(&product).unwrap();
+        assert!(!cache.is_up_to_date("./main", "fp", &product));
+    }
+}
@@ -0,0 +1,281 @@
+use std::ops::Range;
+
+use super::comparator::Comparator;
+
+/// Line of the groundtruth or the output. `number` is 1-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Line<'a> {
+    pub number: usize,
+    pub text: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffRow<'a> {
+    /// Lines regarded as equal by the comparator
+    Same {
+        truth: Line<'a>,
+        output: Line<'a>,
+    },
+
+    /// `first_mismatch` is the index of the first mismatched token,
+    /// or `None` if only whitespaces differ.
+    Changed {
+        truth: Line<'a>,
+        output: Line<'a>,
+        first_mismatch: Option<usize>,
+    },
+
+    TruthOnly(Line<'a>),
+    OutputOnly(Line<'a>),
+
+    /// Number of omitted `Same` rows
+    Collapsed(usize),
+}
+
+/// The LCS table is not built beyond this size, and lines are paired by position instead.
+const MAX_LCS_TABLE_SIZE: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Line-level diff, where two lines are regarded as equal if the comparator accepts them.
+/// Runs of `Same` rows are collapsed except for `context` rows around the changes.
+pub fn diff_lines<'a>(
+    truth: &[&'a str],
+    output: &[&'a str],
+    comparator: Comparator,
+    context: usize,
+) -> Vec<DiffRow<'a>> {
+    let ops = self::line_ops(truth, output, |a, b| comparator.matches(a, b));
+    let truth_line = |i: usize| Line {
+        number: i + 1,
+        text: truth[i],
+    };
+    let output_line = |j: usize| Line {
+        number: j + 1,
+        text: output[j],
+    };
+
+    let mut rows = Vec::new();
+    let mut deleted = Vec::new();
+    let mut inserted = Vec::new();
+    let flush =
+        |rows: &mut Vec<DiffRow<'a>>, deleted: &mut Vec<usize>, inserted: &mut Vec<usize>| {
+            let paired = deleted.len().min(inserted.len());
+            for (&i, &j) in deleted.iter().zip(inserted.iter()) {
+                rows.push(DiffRow::Changed {
+                    truth: truth_line(i),
+                    output: output_line(j),
+                    first_mismatch: self::first_mismatched_token(comparator, truth[i], output[j]),
+                });
+            }
+            rows.extend(
+                deleted[paired..]
+                    .iter()
+                    .map(|&i| DiffRow::TruthOnly(truth_line(i))),
+            );
+            rows.extend(
+                inserted[paired..]
+                    .iter()
+                    .map(|&j| DiffRow::OutputOnly(output_line(j))),
+            );
+            deleted.clear();
+            inserted.clear();
+        };
+    for op in ops {
+        match op {
+            Op::Equal(i, j) => {
+                flush(&mut rows, &mut deleted, &mut inserted);
+                rows.push(DiffRow::Same {
+                    truth: truth_line(i),
+                    output: output_line(j),
+                });
+            }
+            Op::Delete(i) => deleted.push(i),
+            Op::Insert(j) => inserted.push(j),
+        }
+    }
+    flush(&mut rows, &mut deleted, &mut inserted);
+
+    self::collapse_same_rows(rows, context)
+}
+
+fn line_ops(truth: &[&str], output: &[&str], eq: impl Fn(&str, &str) -> bool) -> Vec<Op> {
+    let prefix = truth
+        .iter()
+        .zip(output)
+        .take_while(|(a, b)| eq(a, b))
+        .count();
+    let suffix = truth[prefix..]
+        .iter()
+        .rev()
+        .zip(output[prefix..].iter().rev())
+        .take_while(|(a, b)| eq(a, b))
+        .count();
+    let (n, m) = (
+        truth.len() - prefix - suffix,
+        output.len() - prefix - suffix,
+    );
+
+    let mut ops: Vec<_> = (0..prefix).map(|k| Op::Equal(k, k)).collect();
+    if (n + 1).saturating_mul(m + 1) <= MAX_LCS_TABLE_SIZE {
+        // lcs[i * (m + 1) + j]: length of the LCS of truth[prefix + i..] and output[prefix + j..]
+        let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * (m + 1) + j] = if eq(truth[prefix + i], output[prefix + j]) {
+                    lcs[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && eq(truth[prefix + i], output[prefix + j]) {
+                ops.push(Op::Equal(prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if j == m || (i < n && lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1]) {
+                ops.push(Op::Delete(prefix + i));
+                i += 1;
+            } else {
+                ops.push(Op::Insert(prefix + j));
+                j += 1;
+            }
+        }
+    } else {
+        for k in prefix..prefix + n.max(m) {
+            match (k < prefix + n, k < prefix + m) {
+                (true, true) if eq(truth[k], output[k]) => ops.push(Op::Equal(k, k)),
+                (has_truth, has_output) => {
+                    if has_truth {
+                        ops.push(Op::Delete(k));
+                    }
+                    if has_output {
+                        ops.push(Op::Insert(k));
+                    }
+                }
+            }
+        }
+    }
+    ops.extend((0..suffix).map(|k| Op::Equal(prefix + n + k, prefix + m + k)));
+    ops
+}
+
+fn collapse_same_rows(rows: Vec<DiffRow<'_>>, context: usize) -> Vec<DiffRow<'_>> {
+    let is_same = |row: &DiffRow| matches!(row, DiffRow::Same { .. });
+    let mut keep = vec![false; rows.len()];
+    for (k, _) in rows.iter().enumerate().filter(|(_, row)| !is_same(row)) {
+        let range = k.saturating_sub(context)..rows.len().min(k + context + 1);
+        keep[range].fill(true);
+    }
+
+    let mut collapsed = Vec::new();
+    let mut omitted = Vec::new();
+    let flush_omitted = |collapsed: &mut Vec<_>, omitted: &mut Vec<_>| match omitted.len() {
+        0 => {}
+        // Collapsing a single row doesn't save any space.
+        1 => collapsed.append(omitted),
+        n => {
+            collapsed.push(DiffRow::Collapsed(n));
+            omitted.clear();
+        }
+    };
+    for (row, keep) in rows.into_iter().zip(keep) {
+        if keep {
+            flush_omitted(&mut collapsed, &mut omitted);
+            collapsed.push(row);
+        } else {
+            omitted.push(row);
+        }
+    }
+    flush_omitted(&mut collapsed, &mut omitted);
+    collapsed
+}
+
+/// Index of the first whitespace-separated token which the comparator doesn't accept.
+/// If one line is a prefix of the other, the index just after the shorter line is returned.
+pub fn first_mismatched_token(comparator: Comparator, truth: &str, output: &str) -> Option<usize> {
+    let mut truth_tokens = truth.split_ascii_whitespace();
+    let mut output_tokens = output.split_ascii_whitespace();
+    let mut index = 0;
+    loop {
+        match (truth_tokens.next(), output_tokens.next()) {
+            (Some(a), Some(b)) if comparator.token_matches(a, b) => index += 1,
+            (None, None) => return None,
+            _ => return Some(index),
+        }
+    }
+}
+
+/// Byte range of the `index`-th whitespace-separated token in the line.
+pub fn token_span(line: &str, index: usize) -> Option<Range<usize>> {
+    line.split_ascii_whitespace().nth(index).map(|token| {
+        let start = token.as_ptr() as usize - line.as_ptr() as usize;
+        start..start + token.len()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn line(number: usize, text: &str) -> Line<'_> {
+        Line { number, text }
+    }
+
+    #[test]
+    fn should_diff_lines_by_comparator() {
+        let truth = ["1", "2", "3", "4", "5", "6", "7", "0.5 x"];
+        let output = ["1", "2", "3", "4", "5", "7", "0.5000001 y", "8"];
+        let c: Comparator = "float:1e-6".parse().unwrap();
+        let rows = diff_lines(&truth, &output, c, 1);
+        assert_eq!(
+            rows,
+            vec![
+                DiffRow::Collapsed(4),
+                DiffRow::Same {
+                    truth: line(5, "5"),
+                    output: line(5, "5"),
+                },
+                DiffRow::TruthOnly(line(6, "6")),
+                DiffRow::Same {
+                    truth: line(7, "7"),
+                    output: line(6, "7"),
+                },
+                DiffRow::Changed {
+                    truth: line(8, "0.5 x"),
+                    output: line(7, "0.5000001 y"),
+                    first_mismatch: Some(1),
+                },
+                DiffRow::OutputOnly(line(8, "8")),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_not_collapse_single_row() {
+        let rows = diff_lines(&["a", "b", "c"], &["a", "b", "x"], Comparator::Exact, 1);
+        assert_eq!(rows.len(), 3);
+        assert!(matches!(rows[2], DiffRow::Changed { .. }));
+    }
+
+    #[test]
+    fn should_find_first_mismatched_token() {
+        let c = Comparator::Exact;
+        assert_eq!(first_mismatched_token(c, "1 2 3", "1 2 4"), Some(2));
+        assert_eq!(first_mismatched_token(c, "1 2", "1 2 3"), Some(2));
+        assert_eq!(first_mismatched_token(c, "1 2", "1  2 "), None);
+        assert_eq!(
+            first_mismatched_token(Comparator::IgnoreCase, "Yes no", "YES No"),
+            None
+        );
+        assert_eq!(token_span(" ab  cd", 1), Some(5..7));
+        assert_eq!(token_span(" ab  cd", 2), None);
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+
+use super::{
+    result::ProcessOutput,
+    runner::{TestCommand, TestRunner},
+};
+use crate::str_interp::InterpError;
+
+/// Judge program for interactive problems.
+///
+/// The interactor is invoked as `<run> <input_file> <answer_file>`, and its stdin/stdout are
+/// connected to the stdout/stdin of the solution.
+/// The output is accepted if and only if the interactor exits with code 0.
+#[derive(Debug, Clone)]
+pub struct Interactor {
+    runner: TestRunner,
+}
+
+impl Interactor {
+    pub fn new(cmd: TestCommand) -> Self {
+        Self {
+            runner: TestRunner::new(cmd),
+        }
+    }
+
+    pub fn shell(mut self, shell: impl Into<PathBuf>) -> Self {
+        self.runner = self.runner.shell(shell);
+        self
+    }
+
+    pub fn program_file(
+        mut self,
+        filepath: impl AsRef<Path>,
+    ) -> std::result::Result<Self, InterpError> {
+        self.runner = self.runner.program_file(filepath)?;
+        Ok(self)
+    }
+
+    pub fn get_shell(&self) -> &Path {
+        self.runner.get_shell()
+    }
+
+    pub fn get_runner(&self) -> &TestRunner {
+        &self.runner
+    }
+
+    pub fn get_command(&self) -> &TestCommand {
+        self.runner.get_command()
+    }
+
+    pub fn is_compile_cmd_defined(&self) -> bool {
+        self.runner.is_compile_cmd_defined()
+    }
+
+    pub async fn compile(&self) -> anyhow::Result<ProcessOutput> {
+        self.runner.compile().await
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sender {
+    Solution,
+    Interactor,
+}
+
+/// Record of the exchange between the solution and the interactor.
+#[derive(Debug, Default)]
+pub struct Transcript {
+    chunks: Vec<(Sender, Vec<u8>)>,
+    len: usize,
+    capacity: usize,
+    truncated: bool,
+}
+
+impl Transcript {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    pub fn push(&mut self, sender: Sender, data: &[u8]) {
+        let n = data.len().min(self.capacity - self.len);
+        if n < data.len() {
+            self.truncated = true;
+        }
+        if n == 0 {
+            return;
+        }
+        self.len += n;
+        match self.chunks.last_mut() {
+            Some((last_sender, buf)) if *last_sender == sender => buf.extend(&data[..n]),
+            _ => self.chunks.push((sender, data[..n].to_vec())),
+        }
+    }
+
+    /// Concatenated data sent by `sender`.
+    pub fn data_sent_by(&self, sender: Sender) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .filter(|(s, _)| *s == sender)
+            .flat_map(|(_, data)| data.iter().copied())
+            .collect()
+    }
+
+    /// Render as lines prefixed with `> ` (solution to interactor) or `< ` (interactor to solution).
+    pub fn render(&self) -> String {
+        let mut s = String::new();
+        for (sender, data) in &self.chunks {
+            let prefix = match sender {
+                Sender::Solution => "> ",
+                Sender::Interactor => "< ",
+            };
+            for line in String::from_utf8_lossy(data).lines() {
+                s += prefix;
+                s += line;
+                s.push('\n');
+            }
+        }
+        if self.truncated {
+            s += "...(truncated)\n";
+        }
+        s
+    }
+}
+
+/// Copy all bytes from `from` into `to`, recording them into the transcript.
+/// Keep draining `from` even after `to` is closed so that the sender never blocks.
+pub(super) async fn relay(
+    mut from: impl AsyncRead + Unpin,
+    mut to: impl AsyncWrite + Unpin,
+    sender: Sender,
+    transcript: Arc<Mutex<Transcript>>,
+) -> std::io::Result<()> {
+    let mut buf = vec![0u8; 8192];
+    let mut is_dest_open = true;
+    loop {
+        let n = from.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        transcript.lock().unwrap().push(sender, &buf[..n]);
+        if is_dest_open {
+            is_dest_open = to.write_all(&buf[..n]).await.is_ok() && to.flush().await.is_ok();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn transcript_render() {
+        let mut t = Transcript::with_capacity(64);
+        t.push(Sender::Interactor, b"3\n");
+        t.push(Sender::Solution, b"? 1");
+        t.push(Sender::Solution, b"\n");
+        t.push(Sender::Interactor, b"<\n");
+        t.push(Sender::Solution, b"! 2\n");
+        assert_eq!(t.render(), "< 3\n> ? 1\n< <\n> ! 2\n");
+        assert_eq!(t.data_sent_by(Sender::Solution), b"? 1\n! 2\n");
+    }
+
+    #[test]
+    fn transcript_should_be_truncated() {
+        let mut t = Transcript::with_capacity(4);
+        t.push(Sender::Solution, b"abc\n");
+        t.push(Sender::Interactor, b"def\n");
+        assert_eq!(t.render(), "> abc\n...(truncated)\n");
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    io,
+    os::unix::process::ExitStatusExt as _,
+    process::ExitStatus,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt as _},
+    process::{Child, Command},
+    task::JoinHandle,
+};
+
+/// Resources consumed by a process (including its waited-for descendants).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ResourceUsage {
+    /// Peak resident set size
+    pub peak_memory_kb: u64,
+
+    /// User CPU time + system CPU time
+    pub cpu_time: Duration,
+}
+
+/// Spawn a blocking task which waits for the child process to exit, collecting its resource usage.
+///
+/// The child is reaped by `wait4(2)` in the task, so `Child::wait()` must not be called for it;
+/// use `Child::start_kill()` to kill it.
+pub(super) fn spawn_waiter(child: &Child) -> JoinHandle<io::Result<(ExitStatus, ResourceUsage)>> {
+    let pid = child.id().expect("Child process has already been reaped") as libc::pid_t;
+    tokio::task::spawn_blocking(move || self::wait4(pid))
+}
+
+/// Make the child the leader of a new process group, so that its descendants (e.g. the program
+/// forked by the shell) can be killed together by [`kill_process_group`].
+pub(super) fn new_process_group(cmd: &mut Command) {
+    // SAFETY: setpgid(2) is async-signal-safe.
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setpgid(0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+fn kill_process_group(pgid: u32) -> io::Result<()> {
+    // SAFETY: kill(2) has no memory-safety requirements.
+    let ret = unsafe { libc::kill(-(pgid as libc::pid_t), libc::SIGKILL) };
+    let err = io::Error::last_os_error();
+    if ret != 0 && err.raw_os_error() != Some(libc::ESRCH) {
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Kills the process group when dropped, so that the processes never outlive the future running
+/// them (e.g. when the test run is canceled).
+///
+/// The group is never signaled after the leader is reaped, since the pgid may then be reused by an
+/// unrelated process group. Use [`ProcessGroupGuard::spawn_waiter`] to wait for the leader.
+pub(super) struct ProcessGroupGuard {
+    pgid: u32,
+    leader_reaped: Arc<Mutex<bool>>,
+}
+
+impl ProcessGroupGuard {
+    /// The child must be the leader of the group made by [`new_process_group`].
+    pub fn new(leader: &Child) -> Self {
+        Self {
+            pgid: leader.id().expect("Child process has already been reaped"),
+            leader_reaped: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    /// Same as [`spawn_waiter`] for the leader, but the rest of the group is killed when the
+    /// leader exits, before it is reaped.
+    pub fn spawn_waiter(&self) -> JoinHandle<io::Result<(ExitStatus, ResourceUsage)>> {
+        let pgid = self.pgid;
+        let leader_reaped = self.leader_reaped.clone();
+        tokio::task::spawn_blocking(move || {
+            // The exited leader remains as a zombie, which keeps the pgid from being reused.
+            self::wait_exit(pgid as libc::pid_t)?;
+            self::kill_process_group(pgid)
+                .unwrap_or_else(|e| log::warn!("Failed to kill descendant processes: {:#}", e));
+            let mut reaped = leader_reaped.lock().unwrap();
+            let res = self::wait4(pgid as libc::pid_t);
+            *reaped = res.is_ok();
+            res
+        })
+    }
+
+    /// Kill the whole group, unless the leader has been reaped.
+    pub fn kill(&self) {
+        let reaped = self.leader_reaped.lock().unwrap();
+        if !*reaped {
+            self::kill_process_group(self.pgid)
+                .unwrap_or_else(|e| log::warn!("Failed to kill process group: {:#}", e));
+        }
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Data read from a pipe until EOF.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Captured {
+    /// The first `max_bytes` of the data
+    pub data: Vec<u8>,
+
+    /// Number of bytes read but not kept in `data`
+    pub omitted_bytes: usize,
+}
+
+/// Read the pipe until EOF, keeping only the first `max_bytes`,
+/// so that the writer never blocks on the full pipe.
+pub(super) async fn capture(
+    mut reader: impl AsyncRead + Unpin,
+    max_bytes: usize,
+) -> io::Result<Captured> {
+    let mut captured = Captured::default();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(captured);
+        }
+        let kept = n.min(max_bytes - captured.data.len());
+        captured.data.extend_from_slice(&buf[..kept]);
+        captured.omitted_bytes += n - kept;
+    }
+}
+
+/// Wait for the process to exit, leaving it unreaped.
+fn wait_exit(pid: libc::pid_t) -> io::Result<()> {
+    // SAFETY: `siginfo_t` is a plain C struct, for which all-zero is a valid value.
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: `info` is a valid pointer during the call.
+        let ret = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            )
+        };
+        if ret == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+fn wait4(pid: libc::pid_t) -> io::Result<(ExitStatus, ResourceUsage)> {
+    let mut status = 0;
+    // SAFETY: `rusage` is a plain C struct, for which all-zero is a valid value.
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: `status` and `rusage` are valid pointers during the call.
+        let ret = unsafe { libc::wait4(pid, &mut status, 0, &mut rusage) };
+        if ret == pid {
+            break;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+
+    let timeval_to_duration = |t: libc::timeval| {
+        Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64)
+    };
+
+    // `ru_maxrss` is in kilobytes on Linux, but in bytes on macOS.
+    let peak_memory_kb = if cfg!(target_os = "macos") {
+        rusage.ru_maxrss as u64 / 1024
+    } else {
+        rusage.ru_maxrss as u64
+    };
+
+    Ok((
+        ExitStatus::from_raw(status),
+        ResourceUsage {
+            peak_memory_kb,
+            cpu_time: timeval_to_duration(rusage.ru_utime) + timeval_to_duration(rusage.ru_stime),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use std::process::Stdio;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn waiter_should_kill_descendants_before_reaping_leader() {
+        let mut command = Command::new("/bin/sh");
+        command
+            .args(["-c", "sleep 10 & echo $!"])
+            .stdout(Stdio::piped());
+        self::new_process_group(&mut command);
+        let mut leader = command.spawn().unwrap();
+        let group = ProcessGroupGuard::new(&leader);
+
+        let (status, _) = group.spawn_waiter().await.unwrap().unwrap();
+        assert!(status.success());
+        assert!(*group.leader_reaped.lock().unwrap());
+
+        // The pipe is closed, since the background process holding it has been killed.
+        let stdout = leader.stdout.take().unwrap();
+        let captured = tokio::time::timeout(Duration::from_secs(1), self::capture(stdout, 64))
+            .await
+            .expect("The descendant is still alive")
+            .unwrap();
+        assert!(!captured.data.is_empty());
+    }
+}
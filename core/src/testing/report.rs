@@ -0,0 +1,222 @@
+use std::{fmt::Write as _, path::Path};
+
+use serde::Serialize;
+
+use super::{
+    result::{JudgeCode, TestOutcome},
+    sandbox,
+};
+
+/// Machine-readable format of test results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum ReportFormat {
+    Json,
+    Junit,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonReport<'a> {
+    total: usize,
+    passed: usize,
+    failed: usize,
+    testcases: Vec<JsonTestcase<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonTestcase<'a> {
+    name: &'a str,
+    origin: Option<String>,
+    verdict: String,
+    time_ms: u128,
+    cpu_time_ms: Option<u128>,
+    peak_memory_kb: Option<u64>,
+    exit_code: Option<i32>,
+    signal: Option<String>,
+    reason: Option<&'a str>,
+    stdout: &'a str,
+    stderr: &'a str,
+    stdout_truncated_bytes: usize,
+    stderr_truncated_bytes: usize,
+}
+
+/// Render the results. `suite_name` is used as the name of the test suite in JUnit XML.
+pub fn render_report(format: ReportFormat, suite_name: &str, results: &[TestOutcome]) -> String {
+    match format {
+        ReportFormat::Json => self::render_json(results),
+        ReportFormat::Junit => self::render_junit(suite_name, results),
+    }
+}
+
+pub fn write_report(
+    path: impl AsRef<Path>,
+    format: ReportFormat,
+    suite_name: &str,
+    results: &[TestOutcome],
+) -> fsutil::Result<()> {
+    fsutil::write_with_mkdir(path, self::render_report(format, suite_name, results))
+}
+
+fn render_json(results: &[TestOutcome]) -> String {
+    let report = JsonReport {
+        total: results.len(),
+        passed: results.iter().filter(|r| r.judge == JudgeCode::AC).count(),
+        failed: results.iter().filter(|r| r.judge.is_failure()).count(),
+        testcases: results
+            .iter()
+            .map(|r| JsonTestcase {
+                name: &r.testcase_name,
+                origin: r.origin.map(|o| o.to_string()),
+                verdict: r.judge.to_string(),
+                time_ms: r.execution_time.as_millis(),
+                cpu_time_ms: r.resource_usage.map(|u| u.cpu_time.as_millis()),
+                peak_memory_kb: r.resource_usage.map(|u| u.peak_memory_kb),
+                exit_code: r.output.status,
+                signal: r.output.signal.map(sandbox::signal_name),
+                reason: r.reason.as_deref(),
+                stdout: &r.output.stdout,
+                stderr: &r.output.stderr,
+                stdout_truncated_bytes: r.output.stdout_truncated_bytes,
+                stderr_truncated_bytes: r.output.stderr_truncated_bytes,
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&report).expect("Failed to serialize test report")
+}
+
+fn render_junit(suite_name: &str, results: &[TestOutcome]) -> String {
+    let failures = results.iter().filter(|r| r.judge.is_failure()).count();
+    let total_time: f64 = results.iter().map(|r| r.execution_time.as_secs_f64()).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let suite_attrs = format!(
+        "name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\"",
+        self::escape_xml(suite_name),
+        results.len(),
+        failures,
+        total_time,
+    );
+    writeln!(xml, "<testsuites {}>", suite_attrs).unwrap();
+    writeln!(xml, "  <testsuite {}>", suite_attrs).unwrap();
+
+    for r in results {
+        let classname = match r.origin {
+            Some(origin) => format!("{}.{}", suite_name, origin),
+            None => suite_name.to_owned(),
+        };
+        writeln!(
+            xml,
+            "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">",
+            self::escape_xml(&r.testcase_name),
+            self::escape_xml(&classname),
+            r.execution_time.as_secs_f64(),
+        )
+        .unwrap();
+        if r.judge == JudgeCode::UJ {
+            writeln!(xml, "      <skipped message=\"no expected output\"/>").unwrap();
+        } else if r.judge.is_failure() {
+            let message = match (&r.reason, r.output.status) {
+                (Some(reason), _) => format!("{} ({})", r.judge, reason),
+                (None, Some(code)) => format!("{} (exit code: {})", r.judge, code),
+                (None, None) => r.judge.to_string(),
+            };
+            writeln!(
+                xml,
+                "      <failure type=\"{}\" message=\"{}\"/>",
+                r.judge,
+                self::escape_xml(&message),
+            )
+            .unwrap();
+        }
+        if !r.output.stdout.is_empty() {
+            writeln!(
+                xml,
+                "      <system-out>{}</system-out>",
+                self::escape_xml(&r.output.stdout)
+            )
+            .unwrap();
+        }
+        if !r.output.stderr.is_empty() {
+            writeln!(
+                xml,
+                "      <system-err>{}</system-err>",
+                self::escape_xml(&r.output.stderr)
+            )
+            .unwrap();
+        }
+        writeln!(xml, "    </testcase>").unwrap();
+    }
+
+    writeln!(xml, "  </testsuite>").unwrap();
+    writeln!(xml, "</testsuites>").unwrap();
+    xml
+}
+
+/// Escape special characters, and drop control characters which are not allowed in XML 1.0.
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&apos;",
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::testing::{ProcessOutput, TestcaseOrigin};
+
+    fn outcome(name: &str, judge: JudgeCode, stdout: &str) -> TestOutcome {
+        TestOutcome {
+            testcase_name: name.to_owned(),
+            origin: Some(TestcaseOrigin::Sample),
+            judge,
+            execution_time: Duration::from_millis(12),
+            groundtruth: "3\n".to_owned(),
+            output: ProcessOutput {
+                status: Some(0),
+                stdout: stdout.to_owned(),
+                stderr: String::new(),
+                ..Default::default()
+            },
+            checker_message: None,
+            comparator: None,
+            reason: None,
+            transcript: None,
+            resource_usage: None,
+            timing_stats: None,
+            omitted_lines: Default::default(),
+        }
+    }
+
+    #[test]
+    fn should_render_reports() {
+        let results = [
+            outcome("sample1", JudgeCode::AC, "3\n"),
+            outcome("sample2", JudgeCode::WA, "<4>\n"),
+        ];
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render_report(ReportFormat::Json, "main", &results)).unwrap();
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["testcases"][1]["verdict"], "WA");
+        assert_eq!(json["testcases"][1]["time_ms"], 12);
+        assert_eq!(json["testcases"][1]["exit_code"], 0);
+
+        let xml = render_report(ReportFormat::Junit, "main", &results);
+        assert!(xml.contains(r#"<testsuite name="main" tests="2" failures="1" time="0.024">"#));
+        assert!(xml.contains(r#"<failure type="WA" message="WA (exit code: 0)"/>"#));
+        assert!(xml.contains("<system-out>&lt;4&gt;\n</system-out>"));
+    }
+}
@@ -1,19 +1,107 @@
 use std::time::Duration;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+pub use super::process::ResourceUsage;
+use super::{comparator::Comparator, testcase::TestcaseOrigin};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct ProcessOutput {
     pub status: Option<i32>,
+
+    /// Signal which terminated the process (including via the shell)
+    pub signal: Option<i32>,
+
     pub stdout: String,
     pub stderr: String,
+
+    /// Number of bytes which were output but are not kept in `stdout` for display
+    pub stdout_truncated_bytes: usize,
+    pub stderr_truncated_bytes: usize,
+}
+
+/// Failure of the compile command, with the diagnostics printed by the compiler.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Compile error: {}", match .output.status {
+    Some(code) => format!("exitcode={}", code),
+    None => "process terminated by signal".to_owned(),
+})]
+pub struct CompileError {
+    pub command: String,
+    pub output: ProcessOutput,
 }
 
 #[derive(Debug, Clone)]
 pub struct TestOutcome {
     pub testcase_name: String,
+    pub origin: Option<TestcaseOrigin>,
     pub judge: JudgeCode,
     pub execution_time: Duration,
     pub groundtruth: String,
     pub output: ProcessOutput,
+    pub checker_message: Option<String>,
+
+    /// Comparator which decided the verdict (`None` if judged by the checker or the interactor)
+    pub comparator: Option<Comparator>,
+
+    /// Why the verdict was given (e.g. the signal which killed the process)
+    pub reason: Option<String>,
+
+    /// Exchange between the solution and the interactor (only for interactive problems)
+    pub transcript: Option<String>,
+
+    pub resource_usage: Option<ResourceUsage>,
+
+    /// Statistics of execution times (only when the testcase is run repeatedly)
+    pub timing_stats: Option<TimingStats>,
+
+    /// Lines omitted before `groundtruth` and `output.stdout`, which keep only windows around the
+    /// first mismatch of huge outputs
+    pub omitted_lines: OmittedLines,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OmittedLines {
+    pub groundtruth: usize,
+    pub stdout: usize,
+}
+
+/// Result of running the solution without judging
+#[derive(Debug, Clone)]
+pub struct RunOutcome {
+    pub status: Option<i32>,
+
+    /// Why the process failed (e.g. the signal which killed the process)
+    pub reason: Option<String>,
+
+    pub execution_time: Duration,
+    pub resource_usage: Option<ResourceUsage>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingStats {
+    pub min: Duration,
+    pub median: Duration,
+    pub max: Duration,
+}
+
+impl TimingStats {
+    pub fn from_durations(durations: &[Duration]) -> Option<Self> {
+        let mut sorted = durations.to_vec();
+        sorted.sort();
+        let n = sorted.len();
+        if n == 0 {
+            return None;
+        }
+        let median = if n % 2 == 1 {
+            sorted[n / 2]
+        } else {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2
+        };
+        Some(Self {
+            min: sorted[0],
+            median,
+            max: sorted[n - 1],
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum::Display)]
@@ -21,5 +109,45 @@ pub enum JudgeCode {
     AC,
     WA,
     TLE,
+    MLE,
+    OLE,
     RE,
+    CE,
+
+    /// Unjudged, since the testcase has no expected output
+    UJ,
+}
+
+impl JudgeCode {
+    /// Unjudged testcases are not regarded as failures.
+    pub fn is_failure(self) -> bool {
+        !matches!(self, JudgeCode::AC | JudgeCode::UJ)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timing_stats_from_durations() {
+        let ms = Duration::from_millis;
+        assert_eq!(TimingStats::from_durations(&[]), None);
+        assert_eq!(
+            TimingStats::from_durations(&[ms(30), ms(10), ms(20)]),
+            Some(TimingStats {
+                min: ms(10),
+                median: ms(20),
+                max: ms(30),
+            })
+        );
+        assert_eq!(
+            TimingStats::from_durations(&[ms(40), ms(10), ms(20), ms(30)]),
+            Some(TimingStats {
+                min: ms(10),
+                median: ms(25),
+                max: ms(40),
+            })
+        );
+    }
 }
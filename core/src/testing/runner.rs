@@ -1,21 +1,89 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     ffi::OsStr,
+    os::unix::process::ExitStatusExt as _,
     path::{Path, PathBuf},
     process::Stdio,
+    sync::{Arc, Mutex},
     time::Duration,
 };
 
 use anyhow::{bail, Context};
-use tokio::{io::AsyncReadExt, process::Command};
+use tempfile::TempDir;
+use tokio::{
+    io::AsyncWriteExt as _,
+    process::{Child, Command},
+};
 
-use super::{result::*, testcase::*};
+use super::{
+    checker::*,
+    comparator::Comparator,
+    interactor::{self, Interactor, Sender, Transcript},
+    process::{self, Captured, ResourceUsage},
+    result::*,
+    sandbox::{self, Sandbox},
+    streaming::{self, StreamComparison},
+    testcase::*,
+};
 use crate::str_interp::{interp, InterpError};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct TestCommand {
     pub compile: Option<String>,
     pub run: String,
+
+    /// Environment variables set in addition to the inherited ones
+    pub env: BTreeMap<String, String>,
+
+    /// Working dir of the commands (the current dir if `None`)
+    pub cwd: Option<String>,
+
+    /// Dir of build artifacts, exposed as `#{buildDir}` (`None` if no command refers to it).
+    /// A relative path is resolved from the dir of the program file.
+    pub build_dir: Option<PathBuf>,
+}
+
+impl TestCommand {
+    /// `<shell> -c <script>` with `env` and `cwd` applied
+    pub(crate) fn shell_command(&self, shell: &Path, script: &str) -> Command {
+        let mut command = Command::new(shell);
+        command.args(["-c", script]).envs(&self.env);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command
+    }
+
+    /// Whether the commands, `cwd` or `env` refer to the variable (e.g. `buildDir`).
+    pub fn refers_to_var(&self, name: &str) -> bool {
+        let var = format!("#{{{}}}", name);
+        self.compile
+            .iter()
+            .chain([&self.run])
+            .chain(&self.cwd)
+            .chain(self.env.values())
+            .any(|s| s.contains(&var))
+    }
+
+    /// The file made by `compile`, resolved from `cwd`: the program of `run` if it is given as a
+    /// path (e.g. `./main`), otherwise the output given by `-o <file>` in `compile`.
+    pub fn product_file(&self) -> Option<PathBuf> {
+        let run_program = self
+            .run
+            .split_ascii_whitespace()
+            .next()
+            .filter(|program| program.contains('/'));
+        let compile_output = self.compile.as_deref().and_then(|compile| {
+            let mut words = compile.split_ascii_whitespace();
+            words.find(|&word| word == "-o")?;
+            words.next()
+        });
+        let file = run_program.or(compile_output)?;
+        Some(match &self.cwd {
+            Some(cwd) => Path::new(cwd).join(file),
+            None => PathBuf::from(file),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -23,17 +91,53 @@ pub struct TestRunner {
     cmd: TestCommand,
     shell: PathBuf,
     execution_time_limit: Duration,
+    comparator: Comparator,
+    checker: Option<Box<Checker>>,
+    interactor: Option<Box<Interactor>>,
+    memory_limit_kb: Option<u64>,
+    program_file: Option<PathBuf>,
+    sandbox: Option<Sandbox>,
+}
+
+struct WaitResult {
+    is_timeout: bool,
+    exit_code: Option<i32>,
+    signal: Option<i32>,
+    execution_time: Duration,
+    resource_usage: Option<ResourceUsage>,
+}
+
+/// Stdout of the solution, either compared with the groundtruth while being read or captured.
+enum StdoutResult {
+    Compared(StreamComparison),
+    Captured {
+        stdout: Captured,
+        groundtruth: Option<Captured>,
+    },
 }
 
 impl TestRunner {
     const DEFAULT_SHELL: &str = "/bin/sh";
     const DEFAULT_EXEC_TIME_LIMIT: Duration = Duration::from_millis(1000);
+    const INTERACTOR_GRACE_PERIOD: Duration = Duration::from_millis(3000);
+    /// Output beyond this size is judged as OLE.
+    const OUTPUT_LIMIT_BYTES: usize = 64 * 1024 * 1024;
+    /// Output larger than this factor of the expected answer is also judged as OLE,
+    /// if it exceeds `OUTPUT_EXCESS_MIN_BYTES`.
+    const OUTPUT_EXCESS_FACTOR: usize = 16;
+    const OUTPUT_EXCESS_MIN_BYTES: usize = 1024 * 1024;
 
     pub fn new(cmd: TestCommand) -> Self {
         Self {
             cmd,
             shell: Self::DEFAULT_SHELL.into(),
             execution_time_limit: Self::DEFAULT_EXEC_TIME_LIMIT,
+            comparator: Comparator::default(),
+            checker: None,
+            interactor: None,
+            memory_limit_kb: None,
+            program_file: None,
+            sandbox: None,
         }
     }
 
@@ -47,11 +151,41 @@ impl TestRunner {
         self
     }
 
+    /// Judge as MLE if the peak memory usage exceeds the limit.
+    pub fn memory_limit_kb(mut self, limit: Option<u64>) -> Self {
+        self.memory_limit_kb = limit;
+        self
+    }
+
+    /// Run the solution in the sandbox.
+    pub fn sandbox(mut self, sandbox: Option<Sandbox>) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    pub fn comparator(mut self, comparator: Comparator) -> Self {
+        self.comparator = comparator;
+        self
+    }
+
+    /// Use the checker to judge instead of the comparator.
+    pub fn checker(mut self, checker: Checker) -> Self {
+        self.checker = Some(Box::new(checker));
+        self
+    }
+
+    /// Run the program interacting with the interactor, instead of feeding the input data.
+    pub fn interactor(mut self, interactor: Interactor) -> Self {
+        self.interactor = Some(Box::new(interactor));
+        self
+    }
+
     pub fn program_file(
         mut self,
         filepath: impl AsRef<Path>,
     ) -> std::result::Result<Self, InterpError> {
-        self.cmd = Self::interpolate_command_with_program_file(filepath, self.cmd)?;
+        self.cmd = Self::interpolate_command_with_program_file(&filepath, self.cmd)?;
+        self.program_file = Some(filepath.as_ref().to_owned());
         Ok(self)
     }
 
@@ -60,19 +194,43 @@ impl TestRunner {
         filepath: impl AsRef<Path>,
         mut cmd: TestCommand,
     ) -> std::result::Result<TestCommand, InterpError> {
-        let vars = Self::make_cmd_interp_vars(filepath.as_ref());
+        let filepath = filepath.as_ref();
+        // Made absolute, since the commands may run in another dir (`cwd`).
+        let build_dir = cmd.build_dir.take().map(|dir| {
+            let dir = filepath.parent().unwrap_or(Path::new(".")).join(dir);
+            match std::env::current_dir() {
+                Ok(cur_dir) => fsutil::normalize_path(cur_dir.join(dir)),
+                Err(_) => dir,
+            }
+        });
+        let vars = Self::make_cmd_interp_vars(filepath, build_dir.as_deref());
         cmd.compile = cmd.compile.map(|fmt| interp(&fmt, &vars)).transpose()?;
         cmd.run = interp(&cmd.run, &vars)?;
+        cmd.cwd = cmd.cwd.map(|fmt| interp(&fmt, &vars)).transpose()?;
+        for value in cmd.env.values_mut() {
+            *value = interp(value, &vars)?;
+        }
+        cmd.build_dir = build_dir;
         Ok(cmd)
     }
 
-    fn make_cmd_interp_vars(filepath: &Path) -> HashMap<&'static str, &OsStr> {
+    fn make_cmd_interp_vars<'a>(
+        filepath: &'a Path,
+        build_dir: Option<&'a Path>,
+    ) -> HashMap<&'static str, &'a OsStr> {
         let mut m: HashMap<_, &OsStr> = HashMap::new();
+        if let Some(dir) = build_dir {
+            m.insert("buildDir", dir.as_os_str());
+        }
         m.insert("filePath", filepath.as_ref());
         m.insert("fileName", filepath.file_name().unwrap());
         m.insert(
             "fileDir",
-            filepath.parent().unwrap_or(Path::new(".")).as_os_str(),
+            filepath
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."))
+                .as_os_str(),
         );
         m.insert(
             "fileStem",
@@ -97,22 +255,54 @@ impl TestRunner {
         &self.cmd
     }
 
+    pub fn get_program_file(&self) -> Option<&Path> {
+        self.program_file.as_deref()
+    }
+
     pub fn get_exec_time_limit(&self) -> Duration {
         self.execution_time_limit
     }
 
+    pub fn get_memory_limit_kb(&self) -> Option<u64> {
+        self.memory_limit_kb
+    }
+
+    pub fn get_sandbox(&self) -> Option<&Sandbox> {
+        self.sandbox.as_ref()
+    }
+
+    pub fn get_comparator(&self) -> Comparator {
+        self.comparator
+    }
+
+    pub fn get_checker(&self) -> Option<&Checker> {
+        self.checker.as_deref()
+    }
+
+    pub fn get_interactor(&self) -> Option<&Interactor> {
+        self.interactor.as_deref()
+    }
+
     pub fn is_compile_cmd_defined(&self) -> bool {
         self.cmd.compile.is_some()
     }
 
-    pub async fn compile(&self) -> anyhow::Result<()> {
+    /// Run the compile command, capturing its output.
+    /// Returns the output (e.g. warnings) on success, or [`CompileError`] on failure.
+    pub async fn compile(&self) -> anyhow::Result<ProcessOutput> {
         let Some(cmd) = &self.cmd.compile else {
             bail!("Undefined compile command")
         };
+        if let Some(dir) = &self.cmd.build_dir {
+            fsutil::mkdir_all(dir)?;
+        }
 
-        let status = Command::new(&self.shell)
-            .args(["-c", &cmd])
-            .status()
+        let output = self
+            .cmd
+            .shell_command(&self.shell, cmd)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
             .await
             .with_context(|| {
                 format!(
@@ -122,109 +312,697 @@ impl TestRunner {
                 )
             })?;
 
-        match status.code() {
-            Some(0) => Ok(()),
-            Some(code) => bail!("Compile error: exitcode={}", code),
-            None => bail!("Failed to compile: process terminated by signal"),
+        let output = ProcessOutput {
+            status: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            ..Default::default()
+        };
+        if output.status != Some(0) {
+            return Err(CompileError {
+                command: cmd.to_owned(),
+                output,
+            }
+            .into());
         }
+        Ok(output)
     }
 
-    pub async fn run<'t, T>(
+    /// Run the command with `args`, passing `input` to stdin, and returns the whole output.
+    /// Neither the time limit nor the capture limits are applied.
+    pub async fn exec(
         &self,
-        testcase: &'t T,
-        stdout_capture_max_bytes: usize,
-        stderr_capture_max_bytes: usize,
-    ) -> anyhow::Result<TestOutcome>
-    where
-        T: AsyncTestcase<'t>,
-    {
-        let (mut input_reader, mut groundtruth_reader) = tokio::try_join!(
-            testcase.new_input_reader(),
-            testcase.new_groundtruth_reader()
-        )?;
-
-        let mut stdout_buf = Vec::with_capacity(stdout_capture_max_bytes);
-        let mut stderr_buf = Vec::with_capacity(stderr_capture_max_bytes);
-
-        let mut groundtrugh_buf = Vec::new();
-        let fut_groundtruth_read = tokio::io::copy(&mut groundtruth_reader, &mut groundtrugh_buf);
-
+        args: &[impl AsRef<OsStr>],
+        input: &[u8],
+    ) -> anyhow::Result<std::process::Output> {
         let cmd = &self.cmd.run;
-        let mut proc = Command::new(&self.shell)
-            .args(["-c", &cmd])
+        let mut proc = self
+            .cmd
+            .shell_command(&self.shell, &format!("{} \"$@\"", cmd))
+            .arg("kpr")
+            .args(args)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .kill_on_drop(true)
             .spawn()
             .with_context(|| {
                 format!(
                     "Failed to spawn '{} -c {}'",
                     self.shell.to_string_lossy(),
-                    &cmd
+                    cmd
                 )
             })?;
-        let mut stdout = proc.stdout.take().expect("Failed to open stdout");
-        let mut stderr = proc.stderr.take().expect("Failed to open stderr");
+
         let mut stdin = proc.stdin.take().expect("Failed to open stdin");
+        let write_input = async move {
+            // The process may exit without reading the whole input.
+            let _ = stdin.write_all(input).await;
+        };
+        let (_, output) = tokio::join!(write_input, proc.wait_with_output());
+        output.context("Failed to wait for child process to exit")
+    }
 
-        tokio::io::copy(&mut input_reader, &mut stdin)
-            .await
-            .context("Failed to pass input-data to stdin")?;
-        drop(input_reader);
-        drop(stdin); // NOTE: this line is essential
+    /// Same as [`TestRunner::exec`], but kills the process and its descendants if the time limit
+    /// is exceeded. Returns `None` on timeout.
+    pub async fn exec_within_time_limit(
+        &self,
+        args: &[impl AsRef<OsStr>],
+        input: &[u8],
+    ) -> anyhow::Result<Option<std::process::Output>> {
+        let cmd = &self.cmd.run;
+        let mut command = self
+            .cmd
+            .shell_command(&self.shell, &format!("{} \"$@\"", cmd));
+        command
+            .arg("kpr")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        process::new_process_group(&mut command);
+        let mut proc = command.spawn().with_context(|| {
+            format!(
+                "Failed to spawn '{} -c {}'",
+                self.shell.to_string_lossy(),
+                cmd
+            )
+        })?;
+        let group = process::ProcessGroupGuard::new(&proc);
+
+        let mut stdin = proc.stdin.take().expect("Failed to open stdin");
+        let stdout = proc.stdout.take().expect("Failed to open stdout");
+        let stderr = proc.stderr.take().expect("Failed to open stderr");
+        let write_input = async move {
+            // The process may exit without reading the whole input.
+            let _ = stdin.write_all(input).await;
+        };
+        // The remaining descendants, which may keep the pipes open, are killed by the waiter.
+        let wait = async {
+            let mut waiter = group.spawn_waiter();
+            match tokio::time::timeout(self.execution_time_limit, &mut waiter).await {
+                Ok(res) => (false, res),
+                Err(_) => {
+                    group.kill();
+                    (true, waiter.await)
+                }
+            }
+        };
+        let (_, stdout, stderr, (is_timeout, wait_result)) = tokio::join!(
+            write_input,
+            process::capture(stdout, usize::MAX),
+            process::capture(stderr, usize::MAX),
+            wait
+        );
+        let (status, _) = wait_result?.context("Failed to wait for child process to exit")?;
+        if is_timeout {
+            return Ok(None);
+        }
+        Ok(Some(std::process::Output {
+            status,
+            stdout: stdout.map(|c| c.data).unwrap_or_default(),
+            stderr: stderr.map(|c| c.data).unwrap_or_default(),
+        }))
+    }
 
+    /// Run the solution with the given stdin, inheriting stdout and stderr, without judging.
+    /// The time limit is not applied.
+    pub async fn run_without_judge(&self, stdin: Stdio) -> anyhow::Result<RunOutcome> {
+        let (proc, _sandbox_dir) =
+            self.spawn_solution_with(stdin, Stdio::inherit(), Stdio::inherit(), false)?;
         let start_at = tokio::time::Instant::now();
-        let wait_result = tokio::time::timeout(self.execution_time_limit, proc.wait()).await;
+        let (status, resource_usage) = process::spawn_waiter(&proc)
+            .await?
+            .context("Failed to wait for child process to exit")?;
         let execution_time = tokio::time::Instant::now().duration_since(start_at);
 
-        let (is_timeout, exit_code) = match wait_result {
-            Ok(Ok(status)) => (false, status.code()),
-            Ok(Err(e)) => panic!("Failed to wait for child process to exit: {}", e),
-            Err(_) => {
-                proc.kill()
-                    .await
-                    .unwrap_or_else(|e| log::warn!("Failed to kill TLE process: {:#}", e));
-                (true, None)
-            }
+        let wait_result = WaitResult {
+            is_timeout: false,
+            exit_code: status.code(),
+            signal: status.signal(),
+            execution_time,
+            resource_usage: Some(resource_usage),
         };
+        Ok(RunOutcome {
+            status: wait_result.exit_code,
+            reason: (wait_result.exit_code != Some(0))
+                .then(|| Self::runtime_error_reason(&wait_result)),
+            execution_time,
+            resource_usage: wait_result.resource_usage,
+        })
+    }
 
-        stdout
-            .read_buf(&mut stdout_buf)
-            .await
-            .expect("Failed to capture stdout");
-        stderr
-            .read_buf(&mut stderr_buf)
-            .await
-            .expect("Failed to capture stderr");
+    pub async fn run<'t, T>(
+        &self,
+        testcase: &'t T,
+        stdout_capture_max_bytes: usize,
+        stderr_capture_max_bytes: usize,
+    ) -> anyhow::Result<TestOutcome>
+    where
+        T: AsyncTestcase<'t>,
+    {
+        if let Some(interactor) = &self.interactor {
+            return self
+                .run_interactive(
+                    interactor,
+                    testcase,
+                    stdout_capture_max_bytes,
+                    stderr_capture_max_bytes,
+                )
+                .await;
+        }
 
-        fut_groundtruth_read.await?;
+        // The input file is passed as stdin directly if any, so that we need not copy it.
+        let input_file = match testcase.input_file_path() {
+            Some(path) => Some(
+                std::fs::File::open(path)
+                    .with_context(|| format!("Cannot open input file {:?}", path))?,
+            ),
+            None => None,
+        };
+        let has_groundtruth = testcase.has_groundtruth();
+        let (input_reader, groundtruth_reader) = tokio::try_join!(
+            async {
+                match input_file {
+                    Some(_) => Ok(None),
+                    None => testcase.new_input_reader().await.map(Some),
+                }
+            },
+            async {
+                match has_groundtruth {
+                    true => testcase.new_groundtruth_reader().await.map(Some),
+                    false => Ok(None),
+                }
+            }
+        )?;
+
+        let stdin = input_file.map_or_else(Stdio::piped, Stdio::from);
+        let (mut proc, _sandbox_dir) = self.spawn_solution(stdin)?;
+        let group = process::ProcessGroupGuard::new(&proc);
+        let start_at = tokio::time::Instant::now();
+        let stdout = proc.stdout.take().expect("Failed to open stdout");
+        let stderr = proc.stderr.take().expect("Failed to open stderr");
+        let stdin = proc.stdin.take();
 
-        let groundtruth = String::from_utf8_lossy(&groundtrugh_buf).to_string();
-        let stdout = String::from_utf8_lossy(&stdout_buf).to_string();
-        let stderr = String::from_utf8_lossy(&stderr_buf).to_string();
+        // Drain the pipes while the process is running, so that it never blocks on writing.
+        let capture_stderr = tokio::spawn(process::capture(stderr, stderr_capture_max_bytes));
+        let fut_stdout = async {
+            match groundtruth_reader {
+                // Compare the output with the groundtruth while reading, without holding them
+                Some(reader) if self.checker.is_none() => {
+                    streaming::compare(reader, stdout, self.comparator, stdout_capture_max_bytes)
+                        .await
+                        .map(StdoutResult::Compared)
+                }
+                // The checker needs the whole output
+                reader => {
+                    let max_bytes = match self.checker {
+                        Some(_) => Self::OUTPUT_LIMIT_BYTES,
+                        None => stdout_capture_max_bytes,
+                    };
+                    let (stdout, groundtruth) =
+                        tokio::try_join!(process::capture(stdout, max_bytes), async {
+                            match reader {
+                                Some(reader) => process::capture(reader, stdout_capture_max_bytes)
+                                    .await
+                                    .map(Some),
+                                None => Ok(None),
+                            }
+                        })?;
+                    Ok(StdoutResult::Captured {
+                        stdout,
+                        groundtruth,
+                    })
+                }
+            }
+        };
+        // Write the input while the process is running, so that the time to read it is measured.
+        let fut_input = async {
+            let (Some(mut reader), Some(mut stdin)) = (input_reader, stdin) else {
+                return Ok(());
+            };
+            let res = match tokio::io::copy(&mut reader, &mut stdin).await {
+                // The process may exit without reading the whole input.
+                Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+                res => res
+                    .map(|_| ())
+                    .context("Failed to pass input-data to stdin"),
+            };
+            drop(stdin); // NOTE: this line is essential
+            res
+        };
+        let fut_wait = self.wait_within_time_limit(&group, start_at);
+        let (input_result, stdout_result, wait_result) =
+            tokio::join!(fut_input, fut_stdout, fut_wait);
+        let wait_result = wait_result?;
+        input_result?;
+        let stdout_result = stdout_result.context("Failed to read stdout")?;
+        let stderr_captured = capture_stderr.await?.context("Failed to capture stderr")?;
+        let stderr = String::from_utf8_lossy(&stderr_captured.data).to_string();
 
-        let judge = if is_timeout {
-            JudgeCode::TLE
-        } else if exit_code != Some(0) {
+        let mut checker_message = None;
+        let mut reason = None;
+        let (stdout_len, expected_len) = match &stdout_result {
+            StdoutResult::Compared(cmp) => (cmp.output_len, Some(cmp.groundtruth_len)),
+            // The size of the expected answer is meaningless for the checker.
+            StdoutResult::Captured { stdout, .. } => {
+                (stdout.data.len() + stdout.omitted_bytes, None)
+            }
+        };
+        let judge = if let Some((judge, why)) =
+            self.judge_limit_violation(&wait_result, stdout_len, expected_len)
+        {
+            reason = why;
+            judge
+        } else if wait_result.exit_code != Some(0) {
+            reason = Some(Self::runtime_error_reason(&wait_result));
             JudgeCode::RE
-        } else if stdout != groundtruth {
+        } else {
+            match (&stdout_result, &self.checker) {
+                (StdoutResult::Compared(cmp), _) => match cmp.matched {
+                    true => JudgeCode::AC,
+                    false => JudgeCode::WA,
+                },
+                (StdoutResult::Captured { stdout, .. }, Some(checker)) if has_groundtruth => {
+                    let verdict = Self::check_with_checker(checker, testcase, &stdout.data).await?;
+                    checker_message = Some(verdict.message);
+                    if verdict.accepted {
+                        JudgeCode::AC
+                    } else {
+                        JudgeCode::WA
+                    }
+                }
+                (StdoutResult::Captured { .. }, _) => JudgeCode::UJ,
+            }
+        };
+
+        // Only parts of the outputs are kept for display: the windows around the first mismatch
+        // for WA, otherwise the heads.
+        let (stdout_window, stdout_omitted_bytes, groundtruth_window, omitted_lines) =
+            match &stdout_result {
+                StdoutResult::Compared(cmp) if judge == JudgeCode::WA => (
+                    &cmp.output.data[..],
+                    cmp.output.omitted_bytes,
+                    &cmp.groundtruth.data[..],
+                    OmittedLines {
+                        groundtruth: cmp.groundtruth.omitted_lines,
+                        stdout: cmp.output.omitted_lines,
+                    },
+                ),
+                StdoutResult::Compared(cmp) => (
+                    &cmp.output_head[..],
+                    0,
+                    &cmp.groundtruth_head[..],
+                    OmittedLines::default(),
+                ),
+                StdoutResult::Captured {
+                    stdout,
+                    groundtruth,
+                } => (
+                    &stdout.data[..stdout.data.len().min(stdout_capture_max_bytes)],
+                    0,
+                    groundtruth.as_ref().map_or(&[][..], |g| &g.data[..]),
+                    OmittedLines::default(),
+                ),
+            };
+
+        Ok(TestOutcome {
+            testcase_name: testcase.name().to_owned(),
+            origin: testcase.origin(),
+            judge,
+            execution_time: wait_result.execution_time,
+            groundtruth: String::from_utf8_lossy(groundtruth_window).to_string(),
+            output: ProcessOutput {
+                status: wait_result.exit_code,
+                signal: sandbox::termination_signal(wait_result.exit_code, wait_result.signal),
+                stdout: String::from_utf8_lossy(stdout_window).to_string(),
+                stderr,
+                stdout_truncated_bytes: stdout_len - stdout_omitted_bytes - stdout_window.len(),
+                stderr_truncated_bytes: stderr_captured.omitted_bytes,
+            },
+            checker_message,
+            comparator: self.checker.is_none().then_some(self.comparator),
+            reason,
+            transcript: None,
+            resource_usage: wait_result.resource_usage,
+            timing_stats: None,
+            omitted_lines,
+        })
+    }
+
+    async fn run_interactive<'t, T>(
+        &self,
+        interactor: &Interactor,
+        testcase: &'t T,
+        transcript_max_bytes: usize,
+        stderr_capture_max_bytes: usize,
+    ) -> anyhow::Result<TestOutcome>
+    where
+        T: AsyncTestcase<'t>,
+    {
+        let tmpdir =
+            tempfile::tempdir().context("Failed to create temporary dir for interactor")?;
+        let (input_file, answer_file) = Self::testcase_files(testcase, tmpdir.path()).await?;
+
+        let interactor_shell = interactor.get_shell();
+        let interactor_cmd = &interactor.get_command().run;
+        let mut interactor_command = interactor
+            .get_command()
+            .shell_command(interactor_shell, &format!("{} \"$@\"", interactor_cmd));
+        interactor_command
+            .arg("interactor")
+            .args([&input_file, &answer_file])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // Its descendants may keep the pipes of the relay open, so they are killed together.
+        process::new_process_group(&mut interactor_command);
+        let mut interactor_proc = interactor_command.spawn().with_context(|| {
+            format!(
+                "Failed to spawn interactor '{} -c {}'",
+                interactor_shell.to_string_lossy(),
+                interactor_cmd
+            )
+        })?;
+        let interactor_group = process::ProcessGroupGuard::new(&interactor_proc);
+
+        let (mut proc, _sandbox_dir) = self.spawn_solution(Stdio::piped())?;
+        let group = process::ProcessGroupGuard::new(&proc);
+        let start_at = tokio::time::Instant::now();
+
+        let transcript = Arc::new(Mutex::new(Transcript::with_capacity(transcript_max_bytes)));
+        let relay_to_interactor = tokio::spawn(interactor::relay(
+            proc.stdout.take().expect("Failed to open stdout"),
+            interactor_proc
+                .stdin
+                .take()
+                .expect("Failed to open interactor stdin"),
+            Sender::Solution,
+            transcript.clone(),
+        ));
+        let relay_to_solution = tokio::spawn(interactor::relay(
+            interactor_proc
+                .stdout
+                .take()
+                .expect("Failed to open interactor stdout"),
+            proc.stdin.take().expect("Failed to open stdin"),
+            Sender::Interactor,
+            transcript.clone(),
+        ));
+        let capture_stderr = tokio::spawn(process::capture(
+            proc.stderr.take().expect("Failed to open stderr"),
+            stderr_capture_max_bytes,
+        ));
+        let capture_interactor_stderr = tokio::spawn(process::capture(
+            interactor_proc
+                .stderr
+                .take()
+                .expect("Failed to open interactor stderr"),
+            Self::OUTPUT_LIMIT_BYTES,
+        ));
+
+        let wait_result = self.wait_within_time_limit(&group, start_at).await?;
+
+        // The interactor receives EOF when the solution exits, so it should terminate soon.
+        let mut interactor_waiter = interactor_group.spawn_waiter();
+        let interactor_status =
+            match tokio::time::timeout(Self::INTERACTOR_GRACE_PERIOD, &mut interactor_waiter).await
+            {
+                Ok(res) => {
+                    let (status, _) = res?.context("Failed to wait for interactor to exit")?;
+                    status.code()
+                }
+                Err(_) => {
+                    interactor_group.kill();
+                    interactor_waiter
+                        .await?
+                        .context("Failed to wait for interactor to exit")?;
+                    None
+                }
+            };
+        let _ = tokio::join!(relay_to_interactor, relay_to_solution);
+
+        let stderr_captured = capture_stderr.await?.context("Failed to capture stderr")?;
+        let interactor_stderr = capture_interactor_stderr
+            .await?
+            .context("Failed to capture interactor stderr")?;
+
+        let transcript = transcript.lock().unwrap();
+        let stdout = transcript.data_sent_by(Sender::Solution);
+
+        let stderr = String::from_utf8_lossy(&stderr_captured.data).to_string();
+        let mut interactor_message = String::from_utf8_lossy(&interactor_stderr.data)
+            .trim_end()
+            .to_owned();
+        let mut reason = None;
+        let judge = if let Some((judge, why)) = self.judge_limit_violation(&wait_result, 0, None) {
+            reason = why;
+            judge
+        } else if interactor_status != Some(0) {
+            if interactor_status.is_none() {
+                interactor_message.insert_str(0, "Interactor did not exit normally\n");
+            }
             JudgeCode::WA
+        } else if wait_result.exit_code != Some(0) {
+            reason = Some(Self::runtime_error_reason(&wait_result));
+            JudgeCode::RE
         } else {
             JudgeCode::AC
         };
 
         Ok(TestOutcome {
             testcase_name: testcase.name().to_owned(),
+            origin: testcase.origin(),
             judge,
-            execution_time,
-            groundtruth,
+            execution_time: wait_result.execution_time,
+            groundtruth: String::new(),
             output: ProcessOutput {
-                status: exit_code,
-                stdout,
+                status: wait_result.exit_code,
+                signal: sandbox::termination_signal(wait_result.exit_code, wait_result.signal),
+                stdout: String::from_utf8_lossy(&stdout).to_string(),
                 stderr,
+                stdout_truncated_bytes: 0,
+                stderr_truncated_bytes: stderr_captured.omitted_bytes,
             },
+            checker_message: Some(interactor_message),
+            comparator: None,
+            reason,
+            transcript: Some(transcript.render()),
+            resource_usage: wait_result.resource_usage,
+            timing_stats: None,
+            omitted_lines: Default::default(),
+        })
+    }
+
+    /// Wait for the process to exit, killing it if the time limit since `start_at` is exceeded.
+    /// `start_at` should be the time of spawning, so that the time to pass the input is counted.
+    async fn wait_within_time_limit(
+        &self,
+        group: &process::ProcessGroupGuard,
+        start_at: tokio::time::Instant,
+    ) -> anyhow::Result<WaitResult> {
+        // The remaining descendants, which may keep the pipes open, are killed by the waiter.
+        let mut waiter = group.spawn_waiter();
+        let deadline = start_at + self.execution_time_limit;
+        let wait_result = tokio::time::timeout_at(deadline, &mut waiter).await;
+        let execution_time = tokio::time::Instant::now().duration_since(start_at);
+
+        let (is_timeout, wait_result) = match wait_result {
+            Ok(res) => (false, res),
+            Err(_) => {
+                group.kill();
+                (true, waiter.await)
+            }
+        };
+        let (status, resource_usage) =
+            wait_result?.context("Failed to wait for child process to exit")?;
+
+        Ok(WaitResult {
+            is_timeout,
+            exit_code: if is_timeout { None } else { status.code() },
+            signal: if is_timeout { None } else { status.signal() },
+            execution_time,
+            resource_usage: Some(resource_usage),
         })
     }
+
+    /// Spawn the solution, in a fresh working dir if the sandbox is enabled.
+    /// The dir is removed when the returned `TempDir` is dropped.
+    fn spawn_solution(&self, stdin: Stdio) -> anyhow::Result<(Child, Option<TempDir>)> {
+        self.spawn_solution_with(stdin, Stdio::piped(), Stdio::piped(), true)
+    }
+
+    /// A process in a new process group cannot read from the terminal,
+    /// so `new_process_group` should be false if stdin is inherited.
+    fn spawn_solution_with(
+        &self,
+        stdin: Stdio,
+        stdout: Stdio,
+        stderr: Stdio,
+        new_process_group: bool,
+    ) -> anyhow::Result<(Child, Option<TempDir>)> {
+        let cmd = &self.cmd.run;
+        let mut command = self.cmd.shell_command(&self.shell, cmd);
+        command.stdin(stdin).stdout(stdout).stderr(stderr);
+
+        if new_process_group {
+            process::new_process_group(&mut command);
+        }
+
+        let sandbox_dir = match &self.sandbox {
+            Some(sandbox) => {
+                let base_dir = match &self.cmd.cwd {
+                    Some(cwd) => PathBuf::from(cwd),
+                    None => std::env::current_dir().context("Failed to get current dir")?,
+                };
+                let dir = sandbox
+                    .prepare_working_dir(&base_dir, cmd)
+                    .context("Failed to prepare working dir of sandbox")?;
+                sandbox.apply(&mut command, dir.path());
+                Some(dir)
+            }
+            None => None,
+        };
+
+        let proc = command.spawn().with_context(|| {
+            format!(
+                "Failed to spawn '{} -c {}'",
+                self.shell.to_string_lossy(),
+                &cmd
+            )
+        })?;
+        Ok((proc, sandbox_dir))
+    }
+
+    /// Returns TLE, MLE or OLE (with the reason if any) if the solution violated the limits.
+    fn judge_limit_violation(
+        &self,
+        res: &WaitResult,
+        stdout_len: usize,
+        expected_len: Option<usize>,
+    ) -> Option<(JudgeCode, Option<String>)> {
+        if res.is_timeout {
+            return Some((JudgeCode::TLE, None));
+        }
+        if self.is_memory_limit_exceeded(&res.resource_usage) {
+            return Some((JudgeCode::MLE, None));
+        }
+        if res.exit_code != Some(0) && self.is_address_space_exhausted(&res.resource_usage) {
+            let reason = "address space limit of the sandbox exceeded".to_owned();
+            return Some((JudgeCode::MLE, Some(reason)));
+        }
+        if sandbox::termination_signal(res.exit_code, res.signal) == Some(libc::SIGXFSZ) {
+            let reason = "file size limit exceeded".to_owned();
+            return Some((JudgeCode::OLE, Some(reason)));
+        }
+        if stdout_len > Self::OUTPUT_LIMIT_BYTES {
+            let reason = format!(
+                "output exceeds {} MiB",
+                Self::OUTPUT_LIMIT_BYTES / 1024 / 1024
+            );
+            return Some((JudgeCode::OLE, Some(reason)));
+        }
+        if let Some(expected_len) = expected_len {
+            let limit =
+                (expected_len * Self::OUTPUT_EXCESS_FACTOR).max(Self::OUTPUT_EXCESS_MIN_BYTES);
+            if stdout_len > limit {
+                let reason = format!(
+                    "output of {} bytes is far more than the expected {} bytes",
+                    stdout_len, expected_len
+                );
+                return Some((JudgeCode::OLE, Some(reason)));
+            }
+        }
+        None
+    }
+
+    /// e.g. "killed by SIGFPE: arithmetic error such as division by zero", "exit code 1"
+    fn runtime_error_reason(res: &WaitResult) -> String {
+        match (
+            sandbox::termination_signal(res.exit_code, res.signal),
+            res.exit_code,
+        ) {
+            (Some(signal), _) => match sandbox::signal_explanation(signal) {
+                Some(explanation) => {
+                    format!(
+                        "killed by {}: {}",
+                        sandbox::signal_name(signal),
+                        explanation
+                    )
+                }
+                None => format!("killed by {}", sandbox::signal_name(signal)),
+            },
+            (None, Some(code)) => format!("exit code {}", code),
+            (None, None) => "terminated abnormally".to_owned(),
+        }
+    }
+
+    /// Whether the failure is likely to be caused by the address space limit of the sandbox,
+    /// i.e. the peak memory usage reached half of the limit. Half, since a growing buffer (e.g.
+    /// `std::vector`) needs both the old and the new allocations while being reallocated.
+    fn is_address_space_exhausted(&self, usage: &Option<ResourceUsage>) -> bool {
+        let limit_kb = self.sandbox.and_then(|s| s.address_space_limit_kb);
+        match (limit_kb, usage) {
+            (Some(limit), Some(usage)) => usage.peak_memory_kb * 2 >= limit,
+            _ => false,
+        }
+    }
+
+    fn is_memory_limit_exceeded(&self, usage: &Option<ResourceUsage>) -> bool {
+        match (self.memory_limit_kb, usage) {
+            (Some(limit), Some(usage)) => usage.peak_memory_kb > limit,
+            _ => false,
+        }
+    }
+
+    /// Returns paths of (input, groundtruth) files of the testcase,
+    /// writing them into `tmpdir` if the testcase is not stored as files.
+    async fn testcase_files<'t, T>(
+        testcase: &'t T,
+        tmpdir: &Path,
+    ) -> anyhow::Result<(PathBuf, PathBuf)>
+    where
+        T: AsyncTestcase<'t>,
+    {
+        let input_file = match testcase.input_file_path() {
+            Some(path) => path.to_owned(),
+            None => {
+                let path = tmpdir.join("input.txt");
+                let mut r = testcase.new_input_reader().await?;
+                let mut f = tokio::fs::File::create(&path).await?;
+                tokio::io::copy(&mut r, &mut f).await?;
+                path
+            }
+        };
+        let answer_file = match testcase.groundtruth_file_path() {
+            Some(path) => path.to_owned(),
+            None => {
+                let path = tmpdir.join("answer.txt");
+                let mut r = testcase.new_groundtruth_reader().await?;
+                let mut f = tokio::fs::File::create(&path).await?;
+                tokio::io::copy(&mut r, &mut f).await?;
+                path
+            }
+        };
+        Ok((input_file, answer_file))
+    }
+
+    /// Pass the testcase and the output to the checker, writing them into temporary files if necessary.
+    async fn check_with_checker<'t, T>(
+        checker: &Checker,
+        testcase: &'t T,
+        output: &[u8],
+    ) -> anyhow::Result<CheckerVerdict>
+    where
+        T: AsyncTestcase<'t>,
+    {
+        let tmpdir = tempfile::tempdir().context("Failed to create temporary dir for checker")?;
+        let (input_file, answer_file) = Self::testcase_files(testcase, tmpdir.path()).await?;
+        let output_file = tmpdir.path().join("output.txt");
+        tokio::fs::write(&output_file, output).await?;
+
+        checker.check(&input_file, &output_file, &answer_file).await
+    }
 }
 
 #[cfg(test)]
@@ -235,6 +1013,7 @@ mod test {
         input: &'static str,
         groundtruth: &'static str,
         pyscript: &'static str,
+        comparator: Comparator,
         want_judge: JudgeCode,
         want_output: ProcessOutput,
     }
@@ -246,9 +1025,12 @@ mod test {
             compile: None,
             // terminate '  ->  enclose ' with "  ->  restart '
             run: format!("python3 -c '{}'", x.pyscript.replace("'", r#"'"'"'"#)),
+            ..Default::default()
         };
         let t = OnMemoryTestcase::<&'static str>::new("sample testcase", x.input, x.groundtruth);
-        let r = TestRunner::new(cmd).execution_time_limit(Duration::from_millis(300));
+        let r = TestRunner::new(cmd)
+            .execution_time_limit(Duration::from_millis(300))
+            .comparator(x.comparator);
 
         let res = dbg!(
             r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES)
@@ -256,7 +1038,13 @@ mod test {
         )
         .unwrap();
         assert_eq!(res.judge, x.want_judge);
-        assert_eq!(res.output, x.want_output);
+        // The amount of truncated output depends on the speed of the process.
+        let output = ProcessOutput {
+            stdout_truncated_bytes: 0,
+            stderr_truncated_bytes: 0,
+            ..res.output
+        };
+        assert_eq!(output, x.want_output);
     }
 
     #[tokio::test]
@@ -264,12 +1052,14 @@ mod test {
         run_test(X {
             input: "123\n",
             groundtruth: "hello_123\n",
+            comparator: Comparator::Exact,
             pyscript: r#"print("hello_" + input())"#,
             want_judge: JudgeCode::AC,
             want_output: ProcessOutput {
                 status: Some(0),
                 stdout: "hello_123\n".into(),
                 stderr: "".into(),
+                ..Default::default()
             },
         })
         .await;
@@ -280,12 +1070,14 @@ mod test {
         run_test(X {
             input: "123\n",
             groundtruth: "hello_123\n",
+            comparator: Comparator::Exact,
             pyscript: r#"print("hello_123")"#,
             want_judge: JudgeCode::AC,
             want_output: ProcessOutput {
                 status: Some(0),
                 stdout: "hello_123\n".into(),
                 stderr: "".into(),
+                ..Default::default()
             },
         })
         .await;
@@ -296,12 +1088,14 @@ mod test {
         run_test(X {
             input: "123\n",
             groundtruth: "hello_123\n",
+            comparator: Comparator::Exact,
             pyscript: r#"import sys; print("hello_123", file=sys.stderr)"#,
             want_judge: JudgeCode::WA,
             want_output: ProcessOutput {
                 status: Some(0),
                 stdout: "".into(),
                 stderr: "hello_123\n".into(),
+                ..Default::default()
             },
         })
         .await;
@@ -312,12 +1106,32 @@ mod test {
         run_test(X {
             input: "123\n",
             groundtruth: "hello_123\n",
+            comparator: Comparator::Exact,
             pyscript: r#"print("hello_123", end='')"#,
             want_judge: JudgeCode::WA,
             want_output: ProcessOutput {
                 status: Some(0),
                 stdout: "hello_123".into(),
                 stderr: "".into(),
+                ..Default::default()
+            },
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn should_be_ac_if_comparator_accepts_float_error() {
+        run_test(X {
+            input: "1 3\n",
+            groundtruth: "0.333333333\n",
+            comparator: "float:1e-6".parse().unwrap(),
+            pyscript: r#"a, b = map(int, input().split()); print(a / b)"#,
+            want_judge: JudgeCode::AC,
+            want_output: ProcessOutput {
+                status: Some(0),
+                stdout: "0.3333333333333333\n".into(),
+                stderr: "".into(),
+                ..Default::default()
             },
         })
         .await;
@@ -328,22 +1142,60 @@ mod test {
         run_test(X {
             input: "123\n",
             groundtruth: "hello_123\n",
+            comparator: Comparator::Exact,
             pyscript: r#"print("hello_123"); exit(42)"#,
             want_judge: JudgeCode::RE,
             want_output: ProcessOutput {
                 status: Some(42),
                 stdout: "hello_123\n".into(),
                 stderr: "".into(),
+                ..Default::default()
             },
         })
         .await;
     }
 
+    #[tokio::test]
+    async fn should_record_signal_with_explanation() {
+        let cmd = TestCommand {
+            compile: None,
+            run: "python3 -c 'import os, signal; os.kill(os.getpid(), signal.SIGFPE)'".to_owned(),
+            ..Default::default()
+        };
+        let t = OnMemoryTestcase::<&'static str>::new("sample testcase", "", "");
+        let r = TestRunner::new(cmd).execution_time_limit(Duration::from_millis(3000));
+
+        let res = r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES);
+        let res = dbg!(res.await).unwrap();
+        assert_eq!(res.judge, JudgeCode::RE);
+        assert_eq!(res.output.signal, Some(libc::SIGFPE));
+        assert_eq!(
+            res.reason.as_deref(),
+            Some("killed by SIGFPE: arithmetic error such as division by zero")
+        );
+    }
+
+    #[tokio::test]
+    async fn should_be_ole_if_output_is_far_more_than_expected() {
+        let cmd = TestCommand {
+            compile: None,
+            run: "yes 0123456789 | head -n 200000".to_owned(),
+            ..Default::default()
+        };
+        let t = OnMemoryTestcase::<&'static str>::new("sample testcase", "", "0123456789\n");
+        let r = TestRunner::new(cmd).execution_time_limit(Duration::from_millis(3000));
+
+        let res = r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES);
+        let res = dbg!(res.await).unwrap();
+        assert_eq!(res.judge, JudgeCode::OLE);
+    }
+
     #[tokio::test]
     async fn should_be_tle() {
         run_test(X {
             input: "123\n",
             groundtruth: "hello_123\n",
+            comparator: Comparator::Exact,
             pyscript: "\
 import sys, time
 print('hello', flush=True)
@@ -354,6 +1206,7 @@ time.sleep(0.5)",
                 status: None,
                 stdout: "hello\n".into(),
                 stderr: "world\n".into(),
+                ..Default::default()
             },
         })
         .await;
@@ -364,6 +1217,7 @@ time.sleep(0.5)",
         run_test(X {
             input: "123\n",
             groundtruth: "hello_123\n",
+            comparator: Comparator::Exact,
             pyscript: "\
 import sys
 while True:
@@ -375,8 +1229,445 @@ while True:
                 status: None,
                 stdout: "hello\n".repeat(100)[..STDOUT_CAPTURE_MAX_BYTES].into(),
                 stderr: "world\n".repeat(100)[..STDERR_CAPTURE_MAX_BYTES].into(),
+                ..Default::default()
             },
         })
         .await;
     }
+
+    #[tokio::test]
+    async fn should_drain_large_output_while_running() {
+        // Much larger than the pipe buffer
+        let cmd = TestCommand {
+            compile: None,
+            run: "yes 0123456789 | head -n 200000".to_owned(),
+            ..Default::default()
+        };
+        let t =
+            OnMemoryTestcase::<String>::new("sample testcase", "", "0123456789\n".repeat(200000));
+        let r = TestRunner::new(cmd).execution_time_limit(Duration::from_millis(3000));
+
+        let res = r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES);
+        let res = dbg!(res.await).unwrap();
+        assert_eq!(res.judge, JudgeCode::AC);
+        assert_eq!(res.output.stdout.len(), STDOUT_CAPTURE_MAX_BYTES);
+        assert_eq!(
+            res.output.stdout_truncated_bytes,
+            11 * 200000 - STDOUT_CAPTURE_MAX_BYTES
+        );
+    }
+
+    #[tokio::test]
+    async fn should_measure_time_from_spawn_while_passing_input() {
+        // Much larger than the pipe buffer, which cannot be written before the process reads it
+        let input = "0123456789\n".repeat(100000);
+        let cmd = TestCommand {
+            compile: None,
+            run: "sleep 0.2; wc -l".to_owned(),
+            ..Default::default()
+        };
+        let r = TestRunner::new(cmd).execution_time_limit(Duration::from_millis(3000));
+
+        let t = OnMemoryTestcase::<String>::new("sample testcase", input.clone(), "100000\n");
+        let res = dbg!(
+            r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES)
+                .await
+        )
+        .unwrap();
+        assert_eq!(res.judge, JudgeCode::AC);
+        assert!(res.execution_time >= Duration::from_millis(200));
+
+        // The input file is passed as stdin directly.
+        let tmpdir = tempfile::tempdir().unwrap();
+        let (input_file, groundtruth_file) =
+            (tmpdir.path().join("1.in"), tmpdir.path().join("1.out"));
+        std::fs::write(&input_file, input).unwrap();
+        std::fs::write(&groundtruth_file, "100000\n").unwrap();
+        let t = FsTestcase::new("1", input_file, groundtruth_file);
+        let res = dbg!(
+            r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES)
+                .await
+        )
+        .unwrap();
+        assert_eq!(res.judge, JudgeCode::AC);
+        assert!(res.execution_time >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn should_keep_whole_small_output_of_wa() {
+        let cmd = TestCommand {
+            compile: None,
+            run: "seq 10 | sed 's/^5$/-1/'".to_owned(),
+            ..Default::default()
+        };
+        let groundtruth: String = (1..=10).map(|i| format!("{}\n", i)).collect();
+        let t = OnMemoryTestcase::<String>::new("sample testcase", "", groundtruth.clone());
+        let r = TestRunner::new(cmd);
+
+        let res = r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES);
+        let res = dbg!(res.await).unwrap();
+        assert_eq!(res.judge, JudgeCode::WA);
+        assert_eq!(res.groundtruth, groundtruth);
+        assert_eq!(res.output.stdout, groundtruth.replace("\n5\n", "\n-1\n"));
+        assert_eq!(res.output.stdout_truncated_bytes, 0);
+        assert_eq!(res.omitted_lines, OmittedLines::default());
+    }
+
+    #[tokio::test]
+    async fn should_keep_only_windows_around_mismatch_of_large_output() {
+        let cmd = TestCommand {
+            compile: None,
+            run: "seq 200000 | sed 's/^150000$/-1/'".to_owned(),
+            ..Default::default()
+        };
+        let groundtruth: String = (1..=200000).map(|i| format!("{}\n", i)).collect();
+        let t = OnMemoryTestcase::<String>::new("sample testcase", "", groundtruth.clone());
+        let r = TestRunner::new(cmd).execution_time_limit(Duration::from_millis(3000));
+
+        let res = r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES);
+        let res = dbg!(res.await).unwrap();
+        assert_eq!(res.judge, JudgeCode::WA);
+        assert!(res
+            .groundtruth
+            .starts_with("149997\n149998\n149999\n150000\n150001\n"));
+        assert!(res
+            .output
+            .stdout
+            .starts_with("149997\n149998\n149999\n-1\n150001\n"));
+        assert_eq!(
+            res.omitted_lines,
+            OmittedLines {
+                groundtruth: 149996,
+                stdout: 149996,
+            }
+        );
+        let stdout_len = groundtruth.len() - "150000".len() + "-1".len();
+        let stdout_start = groundtruth.find("\n149997\n").unwrap() + 1;
+        assert_eq!(
+            res.output.stdout_truncated_bytes,
+            stdout_len - stdout_start - res.output.stdout.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn should_be_mle() {
+        // Touch every page so that it is actually resident.
+        let pyscript = r#"
+x = bytearray(64 * 1024 * 1024)
+x[::4096] = b"a" * len(x[::4096])
+print(len(x))
+"#;
+        let cmd = TestCommand {
+            compile: None,
+            run: format!("python3 -c '{}'", pyscript),
+            ..Default::default()
+        };
+        let t = OnMemoryTestcase::<&'static str>::new("sample testcase", "", "67108864\n");
+
+        let r = TestRunner::new(cmd).memory_limit_kb(Some(32 * 1024));
+        let res = r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES);
+        let res = dbg!(res.await).unwrap();
+        assert_eq!(res.judge, JudgeCode::MLE);
+        assert!(res.resource_usage.unwrap().peak_memory_kb > 64 * 1024);
+
+        let r = r.memory_limit_kb(Some(256 * 1024));
+        let res = r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES);
+        let res = dbg!(res.await).unwrap();
+        assert_eq!(res.judge, JudgeCode::AC);
+    }
+
+    #[tokio::test]
+    async fn compile_should_capture_diagnostics() {
+        let cmd = TestCommand {
+            compile: Some("echo 'main.cpp:1:1: error: oops' >&2; exit 1".to_owned()),
+            run: "true".to_owned(),
+            ..Default::default()
+        };
+        let err = TestRunner::new(cmd).compile().await.unwrap_err();
+        let err = err.downcast_ref::<CompileError>().unwrap();
+        assert_eq!(err.output.status, Some(1));
+        assert_eq!(err.output.stderr, "main.cpp:1:1: error: oops\n");
+    }
+
+    #[tokio::test]
+    async fn exec_should_pass_args_and_input() {
+        let cmd = TestCommand {
+            compile: None,
+            run: "python3 -c 'import sys; print(sys.argv[1:], input())'".to_owned(),
+            ..Default::default()
+        };
+        let output = TestRunner::new(cmd).exec(&["42", "x y"], b"hello\n").await;
+        let output = dbg!(output).unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"['42', 'x y'] hello\n");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn canceled_run_should_kill_solution_processes() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let pid_file = tmpdir.path().join("pids");
+        let cmd = TestCommand {
+            compile: None,
+            run: format!("sleep 10 & echo $$ $! > {}; wait", pid_file.display()),
+            ..Default::default()
+        };
+        let r = TestRunner::new(cmd).execution_time_limit(Duration::from_secs(10));
+        let t = OnMemoryTestcase::<&'static str>::new("sample testcase", "", "");
+
+        let mut tasks = tokio::task::JoinSet::new();
+        tasks.spawn(async move {
+            r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES)
+                .await
+        });
+        while !pid_file.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(tasks);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        // A zombie has already been killed.
+        let is_alive = |pid: &str| {
+            std::fs::read_to_string(format!("/proc/{}/stat", pid)).is_ok_and(|stat| {
+                !stat
+                    .rsplit_once(')')
+                    .unwrap()
+                    .1
+                    .trim_start()
+                    .starts_with('Z')
+            })
+        };
+        let pids = std::fs::read_to_string(&pid_file).unwrap();
+        for pid in pids.split_whitespace() {
+            assert!(!is_alive(pid), "Process {} is still alive", pid);
+        }
+    }
+
+    #[tokio::test]
+    async fn exec_within_time_limit_should_kill_hanging_process() {
+        let cmd = TestCommand {
+            compile: None,
+            run: "echo started; sleep 10 & sleep 10".to_owned(),
+            ..Default::default()
+        };
+        let r = TestRunner::new(cmd).execution_time_limit(Duration::from_millis(200));
+        let start_at = std::time::Instant::now();
+        let output = r.exec_within_time_limit(&[] as &[&str], b"").await;
+        assert!(dbg!(output).unwrap().is_none());
+        assert!(start_at.elapsed() < Duration::from_secs(5));
+
+        let cmd = TestCommand {
+            run: "echo".to_owned(),
+            ..Default::default()
+        };
+        let r = TestRunner::new(cmd);
+        let output = r.exec_within_time_limit(&["x"], b"").await;
+        assert_eq!(dbg!(output).unwrap().unwrap().stdout, b"x\n");
+    }
+
+    #[test]
+    fn product_file_should_be_resolved_from_run_or_compile() {
+        let cmd = |compile: &str, run: &str| TestCommand {
+            compile: Some(compile.to_owned()),
+            run: run.to_owned(),
+            cwd: Some("/work".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            cmd("g++ main.cpp", "./a.out").product_file(),
+            Some(PathBuf::from("/work/./a.out"))
+        );
+        assert_eq!(
+            cmd("rustc main.rs -o main -O", "exec main").product_file(),
+            Some(PathBuf::from("/work/main"))
+        );
+        assert_eq!(cmd("javac Main.java", "java Main").product_file(), None);
+    }
+
+    #[tokio::test]
+    async fn should_apply_env_cwd_and_build_dir() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path().canonicalize().unwrap();
+        let cmd = TestCommand {
+            compile: Some("echo built > #{buildDir}/#{fileStem}".to_owned()),
+            run: "cat #{buildDir}/#{fileStem}; echo \"$LANG_NAME\"; pwd".to_owned(),
+            env: [("LANG_NAME".to_owned(), "#{fileExt}".to_owned())].into(),
+            cwd: Some("#{fileDir}".to_owned()),
+            build_dir: Some(".kpr/build".into()),
+        };
+        let r = TestRunner::new(cmd)
+            .program_file(dir.join("main.cpp"))
+            .unwrap();
+        assert_eq!(r.get_command().build_dir, Some(dir.join(".kpr/build")));
+
+        r.compile().await.unwrap();
+        let output = r.exec(&[] as &[&str], b"").await.unwrap();
+        let expected = format!("built\ncpp\n{}\n", dir.display());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    }
+
+    #[tokio::test]
+    async fn build_dir_should_be_resolved_from_program_file_regardless_of_cwd() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path().canonicalize().unwrap();
+        let cur_dir = std::env::current_dir().unwrap().canonicalize().unwrap();
+        let program_file = fsutil::relative_path(&cur_dir, dir.join("sub/main.cpp"));
+        fsutil::mkdir_all(dir.join("sub")).unwrap();
+
+        let cmd = TestCommand {
+            compile: Some("echo built > #{buildDir}/#{fileStem}".to_owned()),
+            run: "cat #{buildDir}/#{fileStem}".to_owned(),
+            cwd: Some("#{fileDir}".to_owned()),
+            build_dir: Some(".kpr/build".into()),
+            ..Default::default()
+        };
+        let r = TestRunner::new(cmd).program_file(&program_file).unwrap();
+        assert_eq!(r.get_command().build_dir, Some(dir.join("sub/.kpr/build")));
+
+        r.compile().await.unwrap();
+        let output = r.exec(&[] as &[&str], b"").await.unwrap();
+        assert_eq!(output.stdout, b"built\n");
+    }
+
+    #[tokio::test]
+    async fn checker_should_decide_judge() {
+        let pyscript = r#"
+import sys
+out = open(sys.argv[2]).read().split()
+ans = open(sys.argv[3]).read().split()
+if sorted(out) != sorted(ans):
+    print("not a permutation of the answer")
+    sys.exit(1)
+"#;
+        let checker = Checker::new(TestCommand {
+            compile: None,
+            run: format!("python3 -c '{}'", pyscript.replace("'", r#"'"'"'"#)),
+            ..Default::default()
+        });
+        let cmd = TestCommand {
+            compile: None,
+            run: "cat".to_owned(),
+            ..Default::default()
+        };
+        let r = TestRunner::new(cmd).checker(checker);
+
+        let t = OnMemoryTestcase::<&'static str>::new("accepted", "3 1 2\n", "1 2 3\n");
+        let res = r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES);
+        let res = dbg!(res.await).unwrap();
+        assert_eq!(res.judge, JudgeCode::AC);
+        assert_eq!(res.checker_message.as_deref(), Some(""));
+
+        let t = OnMemoryTestcase::<&'static str>::new("rejected", "3 1 1\n", "1 2 3\n");
+        let res = r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES);
+        let res = dbg!(res.await).unwrap();
+        assert_eq!(res.judge, JudgeCode::WA);
+        assert_eq!(
+            res.checker_message.as_deref(),
+            Some("not a permutation of the answer")
+        );
+    }
+
+    #[tokio::test]
+    async fn checker_exceeding_time_limit_should_fail() {
+        let checker = Checker::new(TestCommand {
+            compile: None,
+            run: "sleep 1000; :".to_owned(),
+            ..Default::default()
+        })
+        .time_limit(Duration::from_millis(200));
+        let cmd = TestCommand {
+            compile: None,
+            run: "cat".to_owned(),
+            ..Default::default()
+        };
+        let r = TestRunner::new(cmd).checker(checker);
+
+        let t = OnMemoryTestcase::<&'static str>::new("sample testcase", "1\n", "1\n");
+        let res = r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES);
+        let res = dbg!(res.await).unwrap();
+        assert_eq!(res.judge, JudgeCode::WA);
+        assert_eq!(
+            res.checker_message.as_deref(),
+            Some("Checker failed (timeout)")
+        );
+    }
+
+    #[tokio::test]
+    async fn interactor_should_decide_judge() {
+        // The interactor sends N and expects 2N.
+        let pyscript = r#"
+import sys
+n = int(open(sys.argv[1]).read())
+print(n, flush=True)
+ans = int(input())
+if ans != 2 * n:
+    print("expected", 2 * n, "but got", ans, file=sys.stderr)
+    sys.exit(1)
+"#;
+        let interactor = Interactor::new(TestCommand {
+            compile: None,
+            run: format!("python3 -c '{}'", pyscript.replace("'", r#"'"'"'"#)),
+            ..Default::default()
+        });
+        let run_test = |solution: &'static str| {
+            let r = TestRunner::new(TestCommand {
+                compile: None,
+                run: format!("python3 -c '{}'", solution),
+                ..Default::default()
+            })
+            .execution_time_limit(Duration::from_millis(1000))
+            .interactor(interactor.clone());
+            async move {
+                let t = OnMemoryTestcase::<&'static str>::new("interactive", "21\n", "");
+                dbg!(
+                    r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES)
+                        .await
+                )
+                .unwrap()
+            }
+        };
+
+        let res = run_test("print(int(input()) * 2)").await;
+        assert_eq!(res.judge, JudgeCode::AC);
+        assert_eq!(res.transcript.as_deref(), Some("< 21\n> 42\n"));
+
+        let res = run_test("print(int(input()) * 3)").await;
+        assert_eq!(res.judge, JudgeCode::WA);
+        assert_eq!(
+            res.checker_message.as_deref(),
+            Some("expected 42 but got 63")
+        );
+
+        let res = run_test("import time; time.sleep(2)").await;
+        assert_eq!(res.judge, JudgeCode::TLE);
+    }
+
+    #[tokio::test]
+    async fn hanging_interactor_should_be_killed_with_descendants() {
+        // The shell waits for `sleep`, which keeps the pipes open.
+        let interactor = Interactor::new(TestCommand {
+            compile: None,
+            run: "sleep 1000; :".to_owned(),
+            ..Default::default()
+        });
+        let r = TestRunner::new(TestCommand {
+            compile: None,
+            run: "true".to_owned(),
+            ..Default::default()
+        })
+        .interactor(interactor);
+
+        let t = OnMemoryTestcase::<&'static str>::new("interactive", "", "");
+        let res = r.run(&t, STDOUT_CAPTURE_MAX_BYTES, STDERR_CAPTURE_MAX_BYTES);
+        let res = tokio::time::timeout(Duration::from_secs(10), res)
+            .await
+            .expect("The run should finish after the grace period");
+        let res = dbg!(res).unwrap();
+        assert_eq!(res.judge, JudgeCode::WA);
+        assert!(res
+            .checker_message
+            .unwrap()
+            .starts_with("Interactor did not exit normally"));
+    }
 }
@@ -0,0 +1,240 @@
+use std::{io, path::Path};
+
+use tempfile::TempDir;
+use tokio::process::Command;
+
+/// Resource limits and isolation applied to the solution process.
+///
+/// The limits are applied by `setrlimit(2)`, and are clamped to the current hard limits.
+/// `None` means unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sandbox {
+    pub stack_limit_kb: Option<u64>,
+    pub address_space_limit_kb: Option<u64>,
+    pub file_size_limit_kb: Option<u64>,
+
+    /// Note that this limits the number of processes of the user, not only of the solution.
+    pub max_processes: Option<u64>,
+
+    /// Run in a new network namespace (only on Linux, and only where permitted) if false
+    pub allow_network: bool,
+}
+
+impl Sandbox {
+    /// Create a fresh working dir, which has copies of the files in `base_dir` given by relative
+    /// paths in the run command (e.g. `./main`, `main.py`) so that the command keeps working.
+    /// The other files are not exposed, and files created by the solution are removed with the dir.
+    pub(super) fn prepare_working_dir(&self, base_dir: &Path, run: &str) -> io::Result<TempDir> {
+        let dir = tempfile::Builder::new().prefix("kpr-sandbox-").tempdir()?;
+        for word in run.split_ascii_whitespace() {
+            let path = fsutil::normalize_path(word.trim_matches(['\'', '"']));
+            if path.is_absolute() || path.starts_with("..") || path == Path::new(".") {
+                continue;
+            }
+            let src = base_dir.join(&path);
+            if !src.is_file() {
+                continue;
+            }
+            let dest = dir.path().join(&path);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(src, dest)?;
+        }
+        Ok(dir)
+    }
+
+    pub(super) fn apply(&self, cmd: &mut Command, working_dir: &Path) {
+        cmd.current_dir(working_dir);
+
+        let kb = |limit: Option<u64>| limit.map_or(libc::RLIM_INFINITY, |kb| kb * 1024);
+        let limits = [
+            (libc::RLIMIT_STACK, kb(self.stack_limit_kb)),
+            (libc::RLIMIT_AS, kb(self.address_space_limit_kb)),
+            (libc::RLIMIT_FSIZE, kb(self.file_size_limit_kb)),
+            (
+                libc::RLIMIT_NPROC,
+                self.max_processes.unwrap_or(libc::RLIM_INFINITY),
+            ),
+            (libc::RLIMIT_CORE, 0),
+        ];
+        let allow_network = self.allow_network;
+
+        // SAFETY: The closure calls only async-signal-safe syscalls, without allocation.
+        unsafe {
+            cmd.pre_exec(move || {
+                if !allow_network {
+                    self::unshare_network();
+                }
+                for (resource, value) in limits {
+                    self::set_rlimit(resource, value)?;
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+/// Best effort: the network is left available if namespaces are not permitted.
+fn unshare_network() {
+    #[cfg(target_os = "linux")]
+    // SAFETY: unshare(2) has no memory-safety requirements.
+    unsafe {
+        if libc::unshare(libc::CLONE_NEWNET) != 0 {
+            // Unprivileged users may create a network namespace in a new user namespace.
+            libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(not(target_os = "linux"))]
+type RlimitResource = libc::c_int;
+
+fn set_rlimit(resource: RlimitResource, value: libc::rlim_t) -> io::Result<()> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `limit` is a valid pointer during the calls.
+    unsafe {
+        if libc::getrlimit(resource, &mut limit) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let value = value.min(limit.rlim_max);
+        limit.rlim_cur = value;
+        limit.rlim_max = value;
+        if libc::setrlimit(resource, &limit) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Signal which terminated the process.
+/// Since the command is run via the shell, `128 + N` exit code of the shell is also regarded
+/// as termination by the signal N.
+pub fn termination_signal(exit_code: Option<i32>, signal: Option<i32>) -> Option<i32> {
+    match (exit_code, signal) {
+        (_, Some(sig)) => Some(sig),
+        (Some(code), None) if (129..=128 + 64).contains(&code) => Some(code - 128),
+        _ => None,
+    }
+}
+
+/// e.g. "SIGSEGV"
+pub fn signal_name(signal: i32) -> String {
+    let name = match signal {
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGILL => "SIGILL",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGXCPU => "SIGXCPU",
+        libc::SIGXFSZ => "SIGXFSZ",
+        _ => return format!("signal {}", signal),
+    };
+    name.to_owned()
+}
+
+/// Likely cause of the signal, e.g. "division by zero" for SIGFPE
+pub fn signal_explanation(signal: i32) -> Option<&'static str> {
+    let explanation = match signal {
+        libc::SIGSEGV => "stack overflow (deep recursion?) or invalid memory access",
+        libc::SIGFPE => "arithmetic error such as division by zero",
+        libc::SIGABRT => "assertion failure or uncaught exception",
+        libc::SIGBUS => "invalid memory access",
+        libc::SIGILL => "illegal instruction (missing return statement?)",
+        libc::SIGKILL => "killed externally (out of memory?)",
+        libc::SIGPIPE => "wrote to a closed pipe",
+        libc::SIGXCPU => "CPU time limit exceeded",
+        _ => return None,
+    };
+    Some(explanation)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::{JudgeCode, OnMemoryTestcase, TestCommand, TestRunner};
+
+    fn runner(run: &str, sandbox: Sandbox) -> TestRunner {
+        TestRunner::new(TestCommand {
+            compile: None,
+            run: run.to_owned(),
+            ..Default::default()
+        })
+        .sandbox(Some(sandbox))
+    }
+
+    #[tokio::test]
+    async fn should_apply_limits_in_fresh_dir() {
+        let sandbox = Sandbox {
+            stack_limit_kb: Some(4096),
+            file_size_limit_kb: Some(1),
+            ..Default::default()
+        };
+        let t = OnMemoryTestcase::<&'static str>::new("t", "", "4096\n");
+
+        let res = runner("ulimit -s; touch kpr-sandbox-test", sandbox)
+            .run(&t, 1024, 1024)
+            .await;
+        assert_eq!(dbg!(res).unwrap().judge, JudgeCode::AC);
+        assert!(!Path::new("kpr-sandbox-test").exists());
+
+        let res = runner("head -c 4096 /dev/zero > out", sandbox)
+            .run(&t, 1024, 1024)
+            .await;
+        let res = dbg!(res).unwrap();
+        assert_eq!(res.judge, JudgeCode::OLE);
+    }
+
+    #[tokio::test]
+    async fn should_be_mle_only_if_address_space_is_exhausted() {
+        let sandbox = Sandbox {
+            address_space_limit_kb: Some(256 * 1024),
+            ..Default::default()
+        };
+        let t = OnMemoryTestcase::<&'static str>::new("t", "", "");
+
+        let pyscript = "x = []\nwhile True: x.append(b\"a\" * (1 << 20))";
+        let res = runner(&format!("python3 -c '{}' 2>/dev/null", pyscript), sandbox)
+            .run(&t, 1024, 1024)
+            .await;
+        assert_eq!(dbg!(res).unwrap().judge, JudgeCode::MLE);
+
+        let res = runner("echo std::bad_alloc >&2; exit 1", sandbox)
+            .run(&t, 1024, 1024)
+            .await;
+        assert_eq!(dbg!(res).unwrap().judge, JudgeCode::RE);
+    }
+
+    #[tokio::test]
+    async fn should_expose_only_files_in_run_command() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let dir = tmpdir.path();
+        let script = "test -e data.txt && echo visible; echo hacked > run.sh; echo ok";
+        std::fs::write(dir.join("run.sh"), script).unwrap();
+        std::fs::write(dir.join("data.txt"), "data").unwrap();
+
+        let t = OnMemoryTestcase::<&'static str>::new("t", "", "ok\n");
+        let r = TestRunner::new(TestCommand {
+            compile: None,
+            run: "sh ./run.sh".to_owned(),
+            cwd: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        })
+        .sandbox(Some(Sandbox::default()));
+        let res = r.run(&t, 1024, 1024).await;
+        assert_eq!(dbg!(res).unwrap().judge, JudgeCode::AC);
+        assert_eq!(std::fs::read_to_string(dir.join("run.sh")).unwrap(), script);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("data.txt")).unwrap(),
+            "data"
+        );
+    }
+}
@@ -0,0 +1,374 @@
+use anyhow::bail;
+use async_trait::async_trait;
+
+use super::{
+    result::{JudgeCode, TestOutcome},
+    runner::TestRunner,
+    testcase::OnMemoryTestcase,
+};
+
+/// Hook to keep shrunk inputs consistent with the constraints of the problem
+/// (e.g. N on the first line should be equal to the number of the following values).
+#[async_trait]
+pub trait InputValidator: Send + Sync {
+    /// Returns the (possibly fixed) input, or `None` if the input should be rejected.
+    async fn validate(&self, input: String) -> anyhow::Result<Option<String>>;
+}
+
+/// Validator program which receives the input from stdin.
+///
+/// The input is rejected if the program exits with non-zero code or exceeds the time limit.
+/// Otherwise it is replaced with the stdout of the program, unless the stdout is empty.
+#[derive(Debug, Clone)]
+pub struct ProgramValidator {
+    runner: TestRunner,
+}
+
+impl ProgramValidator {
+    pub fn new(runner: TestRunner) -> Self {
+        Self { runner }
+    }
+}
+
+#[async_trait]
+impl InputValidator for ProgramValidator {
+    async fn validate(&self, input: String) -> anyhow::Result<Option<String>> {
+        let output = self
+            .runner
+            .exec_within_time_limit(&[] as &[&str], input.as_bytes())
+            .await?;
+        let Some(output) = output.filter(|o| o.status.success()) else {
+            return Ok(None);
+        };
+        if output.stdout.is_empty() {
+            return Ok(Some(input));
+        }
+        Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShrinkResult {
+    pub input: String,
+    pub outcome: TestOutcome,
+    pub num_trials: usize,
+}
+
+/// Minimize the failing input by delta debugging, keeping the verdict of the program.
+/// The expected output for each candidate is made by the reference program.
+pub struct Shrinker<'r> {
+    runner: &'r TestRunner,
+    reference: &'r TestRunner,
+    validator: Option<Box<dyn InputValidator + 'r>>,
+    stdout_capture_max_bytes: usize,
+    stderr_capture_max_bytes: usize,
+    num_trials: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Granularity {
+    Line,
+    Token,
+}
+
+/// Piece of the input to be removed. Tokens remember the line they belong to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Unit {
+    line: usize,
+    text: String,
+}
+
+impl<'r> Shrinker<'r> {
+    const MAX_PASSES: usize = 10;
+
+    pub fn new(runner: &'r TestRunner, reference: &'r TestRunner) -> Self {
+        Self {
+            runner,
+            reference,
+            validator: None,
+            stdout_capture_max_bytes: 1024,
+            stderr_capture_max_bytes: 1024,
+            num_trials: 0,
+        }
+    }
+
+    pub fn validator(mut self, validator: impl InputValidator + 'r) -> Self {
+        self.validator = Some(Box::new(validator));
+        self
+    }
+
+    pub fn capture_max_bytes(mut self, stdout: usize, stderr: usize) -> Self {
+        self.stdout_capture_max_bytes = stdout;
+        self.stderr_capture_max_bytes = stderr;
+        self
+    }
+
+    pub async fn shrink(mut self, input: String) -> anyhow::Result<ShrinkResult> {
+        let Some(outcome) = self.run(&input).await? else {
+            bail!("The reference program failed or exceeded the time limit on the given input")
+        };
+        if outcome.judge == JudgeCode::AC {
+            bail!("The given input does not fail (judge: AC)");
+        }
+        let target = outcome.judge;
+
+        let mut best = (input, outcome);
+        for _ in 0..Self::MAX_PASSES {
+            let before = best.0.clone();
+            best = self.remove_units(best, Granularity::Line, target).await?;
+            best = self.remove_units(best, Granularity::Token, target).await?;
+            best = self.shrink_numbers(best, target).await?;
+            if best.0 == before {
+                break;
+            }
+        }
+
+        Ok(ShrinkResult {
+            input: best.0,
+            outcome: best.1,
+            num_trials: self.num_trials,
+        })
+    }
+
+    /// ddmin: try removing chunks of units, refining the chunk size when nothing can be removed.
+    async fn remove_units(
+        &mut self,
+        mut best: (String, TestOutcome),
+        granularity: Granularity,
+        target: JudgeCode,
+    ) -> anyhow::Result<(String, TestOutcome)> {
+        let mut n = 2;
+        loop {
+            let units = self::split(&best.0, granularity);
+            if units.is_empty() {
+                break;
+            }
+            n = n.min(units.len());
+            let chunk_size = units.len().div_ceil(n);
+
+            let mut reduced = false;
+            for start in (0..units.len()).step_by(chunk_size) {
+                let end = (start + chunk_size).min(units.len());
+                let candidate = self::render(units[..start].iter().chain(&units[end..]));
+                if let Some(found) = self.try_candidate(candidate, target).await? {
+                    if found.0.len() < best.0.len() {
+                        best = found;
+                        reduced = true;
+                        break;
+                    }
+                }
+            }
+
+            if reduced {
+                n = (n - 1).max(2);
+            } else if n >= units.len() {
+                break;
+            } else {
+                n = (n * 2).min(units.len());
+            }
+        }
+        Ok(best)
+    }
+
+    /// Replace each integer token with a smaller one (0, 1, or the half of it).
+    async fn shrink_numbers(
+        &mut self,
+        mut best: (String, TestOutcome),
+        target: JudgeCode,
+    ) -> anyhow::Result<(String, TestOutcome)> {
+        let num_units = self::split(&best.0, Granularity::Token).len();
+        for i in 0..num_units {
+            // Halving i64 reaches 0 within 64 steps.
+            for _ in 0..64 {
+                let mut units = self::split(&best.0, Granularity::Token);
+                let Some(value) = units.get(i).and_then(|u| u.text.parse::<i64>().ok()) else {
+                    break;
+                };
+
+                let mut shrunk = false;
+                for smaller in [0, 1, value / 2] {
+                    if smaller.unsigned_abs() >= value.unsigned_abs() {
+                        continue;
+                    }
+                    units[i].text = smaller.to_string();
+                    let candidate = self::render(&units);
+                    if let Some(found) = self.try_candidate(candidate, target).await? {
+                        best = found;
+                        shrunk = true;
+                        break;
+                    }
+                }
+                if !shrunk {
+                    break;
+                }
+            }
+        }
+        Ok(best)
+    }
+
+    /// Returns the validated candidate and its outcome if it reproduces the target judge.
+    async fn try_candidate(
+        &mut self,
+        candidate: String,
+        target: JudgeCode,
+    ) -> anyhow::Result<Option<(String, TestOutcome)>> {
+        let candidate = match &self.validator {
+            Some(validator) => match validator.validate(candidate).await? {
+                Some(validated) => validated,
+                None => return Ok(None),
+            },
+            None => candidate,
+        };
+        match self.run(&candidate).await? {
+            Some(outcome) if outcome.judge == target => Ok(Some((candidate, outcome))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns `None` if the reference program fails or exceeds the time limit, which means the
+    /// input is invalid.
+    async fn run(&mut self, input: &str) -> anyhow::Result<Option<TestOutcome>> {
+        self.num_trials += 1;
+        let expected = self
+            .reference
+            .exec_within_time_limit(&[] as &[&str], input.as_bytes())
+            .await?;
+        let Some(expected) = expected.filter(|o| o.status.success()) else {
+            return Ok(None);
+        };
+        let t = OnMemoryTestcase::<Vec<u8>>::new("shrink", input, expected.stdout);
+        let outcome = self
+            .runner
+            .run(
+                &t,
+                self.stdout_capture_max_bytes,
+                self.stderr_capture_max_bytes,
+            )
+            .await?;
+        Ok(Some(outcome))
+    }
+}
+
+fn split(input: &str, granularity: Granularity) -> Vec<Unit> {
+    let lines = input.lines().enumerate();
+    match granularity {
+        Granularity::Line => lines
+            .map(|(line, text)| Unit {
+                line,
+                text: text.to_owned(),
+            })
+            .collect(),
+        Granularity::Token => lines
+            .flat_map(|(line, text)| {
+                text.split_whitespace().map(move |token| Unit {
+                    line,
+                    text: token.to_owned(),
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Join units on the same line with a space. Lines whose units are all removed disappear.
+fn render<'u>(units: impl IntoIterator<Item = &'u Unit>) -> String {
+    let mut s = String::new();
+    let mut cur_line = None;
+    for unit in units {
+        match cur_line {
+            Some(line) if line == unit.line => s.push(' '),
+            Some(_) => s.push('\n'),
+            None => {}
+        }
+        s += &unit.text;
+        cur_line = Some(unit.line);
+    }
+    if cur_line.is_some() {
+        s.push('\n');
+    }
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::testing::TestCommand;
+
+    fn awk(script: &str) -> TestRunner {
+        TestRunner::new(TestCommand {
+            compile: None,
+            run: format!("awk '{}'", script),
+            ..Default::default()
+        })
+    }
+
+    /// Sum of the values except for the first one (N), and the wrong one which caps values at 50.
+    fn reference_and_runner() -> (TestRunner, TestRunner) {
+        (
+            awk("{ for (i = 1; i <= NF; i++) if (NR > 1 || i > 1) s += $i } END { print s + 0 }"),
+            awk("{ for (i = 1; i <= NF; i++) if (NR > 1 || i > 1) s += ($i < 50 ? $i : 50) } END { print s + 0 }"),
+        )
+    }
+
+    #[test]
+    fn split_and_render() {
+        let input = "3\n1  2 3\n\nabc\n";
+        let lines = split(input, Granularity::Line);
+        assert_eq!(lines.len(), 4);
+        assert_eq!(render(&lines), "3\n1  2 3\n\nabc\n");
+
+        let tokens = split(input, Granularity::Token);
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(render(&tokens), "3\n1 2 3\nabc\n");
+        assert_eq!(render(tokens.iter().skip(1).take(2)), "1 2\n");
+    }
+
+    #[tokio::test]
+    async fn should_shrink_to_minimal_failing_input() {
+        let (reference, runner) = reference_and_runner();
+        let res = Shrinker::new(&runner, &reference)
+            .shrink("5\n10 60 20\n5 7\n".to_owned())
+            .await;
+        let res = dbg!(res).unwrap();
+        assert_eq!(res.outcome.judge, JudgeCode::WA);
+        assert_eq!(res.input, "0 60\n");
+    }
+
+    #[tokio::test]
+    async fn validator_should_keep_input_consistent() {
+        // Rewrite N on the first line to the number of the following values.
+        let validator = awk(
+            r#"{ for (i = 1; i <= NF; i++) if (NR > 1 || i > 1) a[n++] = $i }
+            END { if (n == 0) exit 1; print n; for (i = 0; i < n; i++) print a[i] }"#,
+        );
+
+        let (reference, runner) = reference_and_runner();
+        let res = Shrinker::new(&runner, &reference)
+            .validator(ProgramValidator::new(validator))
+            .shrink("5\n10 60 20\n5 7\n".to_owned())
+            .await;
+        let res = dbg!(res).unwrap();
+        assert_eq!(res.outcome.judge, JudgeCode::WA);
+        assert_eq!(res.input, "1\n60\n");
+    }
+
+    #[tokio::test]
+    async fn should_reject_candidate_on_which_validator_hangs() {
+        let validator = TestRunner::new(TestCommand {
+            compile: None,
+            run: "grep -q 20 || sleep 1000".to_owned(),
+            ..Default::default()
+        })
+        .execution_time_limit(Duration::from_millis(200));
+
+        let (reference, runner) = reference_and_runner();
+        let res = Shrinker::new(&runner, &reference)
+            .validator(ProgramValidator::new(validator))
+            .shrink("5\n10 60 20\n5 7\n".to_owned())
+            .await;
+        let res = dbg!(res).unwrap();
+        assert_eq!(res.outcome.judge, JudgeCode::WA);
+        assert!(res.input.contains("20"));
+    }
+}
@@ -0,0 +1,406 @@
+use std::{collections::VecDeque, io};
+
+use tokio::io::{AsyncRead, AsyncReadExt as _};
+
+use super::comparator::Comparator;
+
+/// Number of lines kept before the first mismatch, to be displayed as the context
+const LINES_BEFORE_MISMATCH: usize = 3;
+
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Part of the data kept for display.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Window {
+    pub data: Vec<u8>,
+
+    /// Number of lines before `data`
+    pub omitted_lines: usize,
+
+    /// Number of bytes before `data`
+    pub omitted_bytes: usize,
+}
+
+/// Result of comparing the output with the groundtruth while reading them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamComparison {
+    pub matched: bool,
+    pub groundtruth_len: usize,
+    pub output_len: usize,
+
+    /// The first `window_max_bytes` of each
+    pub groundtruth_head: Vec<u8>,
+    pub output_head: Vec<u8>,
+
+    /// Windows around the first mismatch (the same as the heads if matched or if both of them
+    /// fit in `window_max_bytes`)
+    pub groundtruth: Window,
+    pub output: Window,
+}
+
+/// Compare the output with the groundtruth incrementally, so that neither of them is held in memory.
+/// Both readers are read until EOF, so that the writer never blocks on the full pipe.
+pub async fn compare(
+    groundtruth: impl AsyncRead + Unpin,
+    output: impl AsyncRead + Unpin,
+    comparator: Comparator,
+    window_max_bytes: usize,
+) -> io::Result<StreamComparison> {
+    let mut truth = Stream::new(groundtruth, window_max_bytes);
+    let mut out = Stream::new(output, window_max_bytes);
+
+    let matched = match comparator {
+        Comparator::Exact => self::compare_bytes(&mut truth, &mut out).await?,
+        _ => self::compare_tokens(&mut truth, &mut out, comparator).await?,
+    };
+    let (mut groundtruth, mut output) =
+        tokio::try_join!(truth.finish(matched), out.finish(matched))?;
+    if truth.len <= window_max_bytes && out.len <= window_max_bytes {
+        groundtruth = truth.whole();
+        output = out.whole();
+    }
+
+    Ok(StreamComparison {
+        matched,
+        groundtruth_len: truth.len,
+        output_len: out.len,
+        groundtruth_head: truth.head,
+        output_head: out.head,
+        groundtruth,
+        output,
+    })
+}
+
+async fn compare_bytes<A, B>(truth: &mut Stream<A>, out: &mut Stream<B>) -> io::Result<bool>
+where
+    A: AsyncRead + Unpin,
+    B: AsyncRead + Unpin,
+{
+    loop {
+        if truth.rest().is_empty() {
+            truth.fill().await?;
+        }
+        if out.rest().is_empty() {
+            out.fill().await?;
+        }
+        let (a, b) = (truth.rest(), out.rest());
+        let (a_len, b_len) = (a.len(), b.len());
+        let common = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+
+        truth.consume(common);
+        out.consume(common);
+        if a_len == 0 && b_len == 0 {
+            return Ok(true);
+        }
+        if common < a_len.min(b_len) || a_len == 0 || b_len == 0 {
+            return Ok(false);
+        }
+    }
+}
+
+async fn compare_tokens<A, B>(
+    truth: &mut Stream<A>,
+    out: &mut Stream<B>,
+    comparator: Comparator,
+) -> io::Result<bool>
+where
+    A: AsyncRead + Unpin,
+    B: AsyncRead + Unpin,
+{
+    loop {
+        match tokio::try_join!(truth.peek_token(), out.peek_token())? {
+            (None, None) => return Ok(true),
+            (Some(a), Some(b)) if comparator.bytes_token_matches(truth.head(a), out.head(b)) => {
+                truth.consume(a);
+                out.consume(b);
+            }
+            _ => return Ok(false),
+        }
+    }
+}
+
+/// Reader which remembers the first bytes and the last lines consumed.
+struct Stream<R> {
+    reader: R,
+    buf: Vec<u8>,
+    pos: usize,
+    eof: bool,
+
+    /// Number of bytes read
+    len: usize,
+
+    max_bytes: usize,
+    head: Vec<u8>,
+
+    /// The last complete lines consumed
+    recent_lines: VecDeque<Vec<u8>>,
+
+    /// The consumed part of the current line, whose head is dropped if too long
+    current_line: Vec<u8>,
+
+    /// Number of complete lines consumed
+    consumed_lines: usize,
+}
+
+impl<R: AsyncRead + Unpin> Stream<R> {
+    fn new(reader: R, max_bytes: usize) -> Self {
+        Self {
+            reader,
+            buf: Vec::new(),
+            pos: 0,
+            eof: false,
+            len: 0,
+            max_bytes,
+            head: Vec::new(),
+            recent_lines: VecDeque::new(),
+            current_line: Vec::new(),
+            consumed_lines: 0,
+        }
+    }
+
+    /// Read the next chunk, appending it to the unconsumed data. Returns `false` at EOF.
+    async fn fill(&mut self) -> io::Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+
+        let start = self.buf.len();
+        self.buf.resize(start + CHUNK_BYTES, 0);
+        let n = self.reader.read(&mut self.buf[start..]).await?;
+        self.buf.truncate(start + n);
+
+        let kept = n.min(self.max_bytes.saturating_sub(self.head.len()));
+        self.head.extend_from_slice(&self.buf[start..start + kept]);
+        self.len += n;
+        self.eof = n == 0;
+        Ok(!self.eof)
+    }
+
+    fn rest(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    fn head(&self, len: usize) -> &[u8] {
+        &self.rest()[..len]
+    }
+
+    fn consume(&mut self, len: usize) {
+        let bytes = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        for segment in bytes.split_inclusive(|&b| b == b'\n') {
+            self.current_line.extend_from_slice(segment);
+            if segment.ends_with(b"\n") {
+                self.consumed_lines += 1;
+                // Reuse the buffer of the oldest line
+                let next_line = match self.recent_lines.len() >= LINES_BEFORE_MISMATCH {
+                    true => self.recent_lines.pop_front().map(|mut line| {
+                        line.clear();
+                        line
+                    }),
+                    false => None,
+                };
+                let line = std::mem::replace(&mut self.current_line, next_line.unwrap_or_default());
+                self.recent_lines.push_back(line);
+            } else if self.current_line.len() > 2 * self.max_bytes {
+                let excess = self.current_line.len() - self.max_bytes;
+                self.current_line.drain(..excess);
+            }
+        }
+    }
+
+    /// Skip whitespaces and read the whole next token. Returns its length, or `None` at EOF.
+    async fn peek_token(&mut self) -> io::Result<Option<usize>> {
+        loop {
+            let spaces = self
+                .rest()
+                .iter()
+                .take_while(|b| b.is_ascii_whitespace())
+                .count();
+            self.consume(spaces);
+
+            let rest = self.rest();
+            if let Some(len) = rest.iter().position(|b| b.is_ascii_whitespace()) {
+                return Ok(Some(len));
+            }
+            if self.eof {
+                return Ok((!rest.is_empty()).then_some(rest.len()));
+            }
+            self.fill().await?;
+        }
+    }
+
+    /// Make the window to display, and read the rest until EOF.
+    async fn finish(&mut self, matched: bool) -> io::Result<Window> {
+        let window = match matched {
+            true => self.whole(),
+            false => self.mismatch_window().await?,
+        };
+        while self.fill().await? {
+            self.pos = self.buf.len();
+        }
+        Ok(window)
+    }
+
+    /// Window of the head, which is the whole data if it fits in `max_bytes`.
+    fn whole(&self) -> Window {
+        Window {
+            data: self.head.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Window of the last lines consumed and the following data, which contains at least the rest
+    /// of the current line unless it is too long.
+    async fn mismatch_window(&mut self) -> io::Result<Window> {
+        let omitted_lines = self.consumed_lines - self.recent_lines.len();
+        let mut data: Vec<u8> = self.recent_lines.iter().flatten().copied().collect();
+        data.extend_from_slice(&self.current_line);
+        let omitted_bytes = self.len - self.rest().len() - data.len();
+
+        let mut following = 0;
+        let mut line_completed = false;
+        loop {
+            let rest = self.rest();
+            let take = rest.len().min(self.max_bytes - following);
+            data.extend_from_slice(&rest[..take]);
+            line_completed |= rest[..take].contains(&b'\n');
+            self.pos += take;
+            following += take;
+
+            let enough =
+                following >= self.max_bytes || (data.len() >= self.max_bytes && line_completed);
+            if enough || !self.fill().await? {
+                break;
+            }
+        }
+        Ok(Window {
+            data,
+            omitted_lines,
+            omitted_bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::AsyncWriteExt as _;
+
+    use super::*;
+
+    /// Reader which yields the data in tiny chunks, so that tokens are split across them.
+    fn tiny_chunks(data: &'static [u8]) -> impl AsyncRead + Unpin {
+        let (mut writer, reader) = tokio::io::duplex(3);
+        tokio::spawn(async move { writer.write_all(data).await });
+        reader
+    }
+
+    #[tokio::test]
+    async fn should_compare_streams_split_into_chunks() {
+        struct X {
+            groundtruth: &'static [u8],
+            output: &'static [u8],
+            comparator: Comparator,
+            want: bool,
+        }
+        let tests = [
+            X {
+                groundtruth: b"123456 789\n",
+                output: b"123456  789\n",
+                comparator: Comparator::Exact,
+                want: false,
+            },
+            X {
+                groundtruth: b"123456 789\n",
+                output: b"123456\n789",
+                comparator: Comparator::Token,
+                want: true,
+            },
+            X {
+                groundtruth: b"3.1415926 \xff\xfe\n",
+                output: b"3.14159259 \xff\xfe",
+                comparator: "float".parse().unwrap(),
+                want: true,
+            },
+            X {
+                groundtruth: b"\xff\xfe\n",
+                output: b"\xff\xfe\n",
+                comparator: Comparator::Exact,
+                want: true,
+            },
+            X {
+                groundtruth: b"Yes\nNo\n",
+                output: b"yes\nno\nno\n",
+                comparator: Comparator::IgnoreCase,
+                want: false,
+            },
+        ];
+        for t in tests {
+            let got = compare(
+                tiny_chunks(t.groundtruth),
+                tiny_chunks(t.output),
+                t.comparator,
+                1024,
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                got.matched,
+                t.want,
+                "{:?}",
+                String::from_utf8_lossy(t.output)
+            );
+            assert_eq!(got.groundtruth_len, t.groundtruth.len());
+            assert_eq!(got.output_len, t.output.len());
+        }
+    }
+
+    #[tokio::test]
+    async fn should_keep_windows_around_first_mismatch() {
+        let groundtruth: String = (1..=1000).map(|i| format!("{}\n", i)).collect();
+        let output = groundtruth.replacen("500\n", "-500\n", 1);
+        let got = compare(
+            groundtruth.as_bytes(),
+            output.as_bytes(),
+            Comparator::Exact,
+            20,
+        )
+        .await
+        .unwrap();
+
+        assert!(!got.matched);
+        assert_eq!(got.output_len, groundtruth.len() + 1);
+        assert_eq!(
+            got.groundtruth,
+            Window {
+                data: b"497\n498\n499\n500\n501\n502\n503\n504\n".to_vec(),
+                omitted_lines: 496,
+                omitted_bytes: groundtruth.find("497\n").unwrap(),
+            }
+        );
+        assert_eq!(
+            got.output,
+            Window {
+                data: b"497\n498\n499\n-500\n501\n502\n503\n504".to_vec(),
+                omitted_lines: 496,
+                omitted_bytes: groundtruth.find("497\n").unwrap(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn should_keep_whole_data_if_it_fits_in_window() {
+        let groundtruth = b"1\n2\n3\n4\n5\n6\n";
+        let output = b"1\n2\n3\n4\n-5\n6\n";
+        let got = compare(&groundtruth[..], &output[..], Comparator::Exact, 20)
+            .await
+            .unwrap();
+
+        assert!(!got.matched);
+        assert_eq!(got.groundtruth.data, groundtruth);
+        assert_eq!(got.output.data, output);
+        assert_eq!(got.output.omitted_lines, 0);
+        assert_eq!(got.output.omitted_bytes, 0);
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    cmp::Ordering,
     fs::File,
     io::{Cursor, Read},
     path::{Path, PathBuf},
@@ -21,6 +22,36 @@ pub trait AsyncTestcase<'a> {
     fn name(&self) -> &str;
     async fn new_input_reader(&'a self) -> anyhow::Result<Self::Reader>;
     async fn new_groundtruth_reader(&'a self) -> anyhow::Result<Self::Reader>;
+
+    /// Returns the path of input data if the testcase is stored as a file.
+    fn input_file_path(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Returns the path of groundtruth data if the testcase is stored as a file.
+    fn groundtruth_file_path(&self) -> Option<&Path> {
+        None
+    }
+
+    /// The output is not judged if false.
+    fn has_groundtruth(&self) -> bool {
+        true
+    }
+
+    fn origin(&self) -> Option<TestcaseOrigin> {
+        None
+    }
+}
+
+/// Where the testcase comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum TestcaseOrigin {
+    /// Fetched from the problem page
+    #[default]
+    Sample,
+    /// Added by the user
+    User,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -28,6 +59,7 @@ pub struct FsTestcase {
     name: String,
     input_data_path: PathBuf,
     groundtruth_data_path: PathBuf,
+    origin: TestcaseOrigin,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -92,10 +124,32 @@ impl<'a> AsyncTestcase<'a> for FsTestcase {
                 )
             })
     }
+
+    fn input_file_path(&self) -> Option<&Path> {
+        Some(&self.input_data_path)
+    }
+
+    fn groundtruth_file_path(&self) -> Option<&Path> {
+        Some(&self.groundtruth_data_path)
+    }
+
+    /// The groundtruth file is missing for input-only testcases.
+    fn has_groundtruth(&self) -> bool {
+        self.groundtruth_data_path.is_file()
+    }
+
+    fn origin(&self) -> Option<TestcaseOrigin> {
+        Some(self.origin)
+    }
 }
 
 pub trait FsTestcaseFinder {
     fn find_by_input_file_path(&self, path: impl AsRef<Path>) -> Option<FsTestcase>;
+
+    /// Depth of subdirs to search for input files (0: only the files directly in the dir)
+    fn max_depth(&self) -> usize {
+        0
+    }
 }
 
 impl FsTestcase {
@@ -108,30 +162,83 @@ impl FsTestcase {
             name: name.into(),
             input_data_path: input.into(),
             groundtruth_data_path: output.into(),
+            origin: TestcaseOrigin::default(),
         }
     }
 
+    pub fn with_origin(mut self, origin: TestcaseOrigin) -> Self {
+        self.origin = origin;
+        self
+    }
+
     pub fn enumerate(
         dir: impl AsRef<Path>,
         finder: &impl FsTestcaseFinder,
     ) -> fsutil::Result<Vec<Self>> {
         let mut res = Vec::new();
-        for entry in fsutil::read_dir(&dir)?.filter_map(Result::ok) {
-            let Ok(ft) =  entry.file_type() else {
-                    continue
-                };
-            if ft.is_dir() {
-                continue;
+        let mut dirs = vec![(dir.as_ref().to_owned(), 0)];
+        while let Some((dir, depth)) = dirs.pop() {
+            for entry in fsutil::read_dir(&dir)?.filter_map(Result::ok) {
+                let Ok(ft) = entry.file_type() else { continue };
+                if ft.is_dir() {
+                    if depth < finder.max_depth() {
+                        dirs.push((entry.path(), depth + 1));
+                    }
+                    continue;
+                }
+                if let Some(t) = finder.find_by_input_file_path(entry.path()) {
+                    res.push(t)
+                }
             }
-            if let Some(t) = finder.find_by_input_file_path(entry.path()) {
-                res.push(t)
+        }
+        res.sort_by(|a, b| self::natural_cmp(&a.name, &b.name));
+        Ok(res)
+    }
+
+    /// Enumerate testcases in each dir, tagging them with the origin. Nonexistent dirs are skipped.
+    pub fn enumerate_merged<P: AsRef<Path>>(
+        sources: &[(P, TestcaseOrigin)],
+        finder: &impl FsTestcaseFinder,
+    ) -> fsutil::Result<Vec<Self>> {
+        let mut res = Vec::new();
+        for (dir, origin) in sources {
+            if !dir.as_ref().is_dir() {
+                continue;
             }
+            let testcases = Self::enumerate(dir, finder)?;
+            res.extend(testcases.into_iter().map(|t| t.with_origin(*origin)));
         }
-        res.sort_by(|a, b| a.name.cmp(&b.name));
         Ok(res)
     }
 }
 
+/// Compare the names treating digit runs as numbers (e.g. "sample2" < "sample10").
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(ca), Some(cb)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        let ordering = if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let split =
+                |s| str::split_at(s, s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+            let ((da, ra), (db, rb)) = (split(a), split(b));
+            (a, b) = (ra, rb);
+            let (ta, tb) = (da.trim_start_matches('0'), db.trim_start_matches('0'));
+            // Fewer leading zeros first if equal in value (e.g. "1" < "01")
+            (ta.len(), ta)
+                .cmp(&(tb.len(), tb))
+                .then(da.len().cmp(&db.len()))
+        } else {
+            (a, b) = (&a[ca.len_utf8()..], &b[cb.len_utf8()..]);
+            ca.cmp(&cb)
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+}
+
 impl<B> OnMemoryTestcase<B>
 where
     B: AsRef<[u8]>,
@@ -103,6 +103,26 @@ impl<'a> Expander<'a> {
         s
     }
 
+    /// Returns paths of the header files which the source depends on: the expanded ones and
+    /// the ones found in the header search dirs but not expanded (e.g. matched to `expansion_ignores`).
+    pub fn get_dependent_header_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.expanded_header_abs_paths.iter().cloned().collect();
+        for (literal_header_path, _, header_full_path) in &self.include_directive_occurrences {
+            if self.expanded_header_abs_paths.contains(header_full_path) {
+                continue;
+            }
+            let found = self
+                .header_serch_dirs
+                .iter()
+                .map(|dir| fsutil::normalize_path(dir.join(literal_header_path)))
+                .find(|path| path.is_file());
+            paths.extend(found);
+        }
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
     fn may_expand(&self, literal_header_path: impl AsRef<str>, mode: HeaderSearchMode) -> bool {
         let literal_header_path = literal_header_path.as_ref();
 